@@ -0,0 +1,249 @@
+/*
+ * File: acquisition.rs
+ * Project: src
+ * Created Date: 18/10/2026
+ * Author: Shun Suzuki
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: Shun Suzuki (suzuki@hapis.k.u-tokyo.ac.jp)
+ * -----
+ * Copyright (c) 2026 Shun Suzuki. All rights reserved.
+ *
+ */
+
+#[cfg(feature = "async")]
+use std::task::{Context, Poll};
+use std::{
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError},
+        Arc, Condvar, Mutex,
+    },
+    task::Waker,
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use pico_common::PicoStatus;
+
+use crate::{driver::BlockReady, Error, Result};
+
+#[derive(Default)]
+struct ReadyState {
+    status: Option<PicoStatus>,
+    cancelled: bool,
+    closed: bool,
+    // the ready callback was dropped without being called
+    superseded: bool,
+    waker: Option<Waker>,
+}
+
+// Completes the signal with `Error::Superseded` if the ready callback is dropped without being called.
+struct Completion(Option<Arc<ReadySignal>>);
+
+impl Drop for Completion {
+    fn drop(&mut self) {
+        if let Some(signal) = self.0.take() {
+            signal.complete(|state| state.superseded = true);
+        }
+    }
+}
+
+// Completion of a block capture, set by the ready callback from a driver thread,
+// or by closing the unit since the driver does not call back afterwards.
+#[derive(Default)]
+pub(crate) struct ReadySignal {
+    state: Mutex<ReadyState>,
+    ready: Condvar,
+}
+
+impl ReadySignal {
+    pub(crate) fn callback(self: &Arc<Self>) -> BlockReady {
+        let mut completion = Completion(Some(self.clone()));
+        Box::new(move |status| {
+            if let Some(signal) = completion.0.take() {
+                signal.complete(|state| state.status = Some(status));
+            }
+        })
+    }
+
+    fn complete(&self, f: impl FnOnce(&mut ReadyState)) {
+        let mut state = self.state.lock().unwrap();
+        f(&mut state);
+        self.ready.notify_all();
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+    }
+
+    fn cancel(&self) {
+        self.state.lock().unwrap().cancelled = true;
+        self.ready.notify_all();
+    }
+
+    pub(crate) fn close(&self) {
+        self.complete(|state| state.closed = true);
+    }
+
+    // The error of a capture that can no longer complete.
+    fn aborted(state: &ReadyState) -> Option<Error> {
+        if state.closed {
+            Some(Error::DeviceClosed)
+        } else if state.superseded {
+            Some(Error::Superseded)
+        } else {
+            None
+        }
+    }
+
+    fn status(status: PicoStatus) -> Result<()> {
+        match status {
+            PicoStatus::OK => Ok(()),
+            status => Err(Error::driver("ps4000RunBlock", status)),
+        }
+    }
+
+    // Blocks until the capture completes or `deadline` passes, or returns `None` if it is cancelled.
+    pub(crate) fn wait(&self, deadline: Option<Instant>) -> Option<Result<()>> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if state.cancelled {
+                return None;
+            }
+            if let Some(status) = state.status {
+                return Some(Self::status(status));
+            }
+            if let Some(e) = Self::aborted(&state) {
+                return Some(Err(e));
+            }
+            state = match deadline {
+                Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                    Some(left) if !left.is_zero() => {
+                        self.ready.wait_timeout(state, left).unwrap().0
+                    }
+                    _ => return Some(Err(Error::Timeout)),
+                },
+                None => self.ready.wait(state).unwrap(),
+            };
+        }
+    }
+
+    #[cfg(feature = "async")]
+    pub(crate) fn poll(&self, cx: &mut Context<'_>) -> Poll<Result<()>> {
+        let mut state = self.state.lock().unwrap();
+        if let Some(status) = state.status {
+            return Poll::Ready(Self::status(status));
+        }
+        if let Some(e) = Self::aborted(&state) {
+            return Poll::Ready(Err(e));
+        }
+        state.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+/// A block acquisition running on a worker thread.
+///
+/// Dropping the handle before the data is ready cancels the acquisition.
+/// Starting another capture on the device before this one completes makes it return [`Error::Superseded`].
+pub struct AcquisitionHandle<T> {
+    signal: Arc<ReadySignal>,
+    stop: Box<dyn Fn() -> Result<()> + Send>,
+    rx: Option<Receiver<Result<T>>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl<T: Send + 'static> AcquisitionHandle<T> {
+    // Waits on the worker thread for `signal` to be set by the driver, then runs `retrieve`.
+    pub(crate) fn spawn(
+        signal: Arc<ReadySignal>,
+        stop: impl Fn() -> Result<()> + Send + 'static,
+        retrieve: impl FnOnce() -> Result<T> + Send + 'static,
+    ) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let worker = {
+            let signal = signal.clone();
+            std::thread::spawn(move || {
+                let result = match signal.wait(None) {
+                    Some(ready) => ready.and_then(|_| retrieve()),
+                    None => return,
+                };
+                let _ = tx.send(result);
+            })
+        };
+
+        Self {
+            signal,
+            stop: Box::new(stop),
+            rx: Some(rx),
+            worker: Some(worker),
+        }
+    }
+}
+
+impl<T> AcquisitionHandle<T> {
+    /// Whether the data has been retrieved, so that waiting does not block.
+    pub fn is_ready(&self) -> bool {
+        self.worker.as_ref().is_none_or(|w| w.is_finished())
+    }
+
+    /// Blocks until the data is ready.
+    pub fn wait(mut self) -> Result<T> {
+        self.recv(None)
+    }
+
+    /// Blocks until the data is ready or `timeout` elapses, in which case [`Error::Timeout`] is returned
+    /// and the handle can be waited on again or cancelled.
+    ///
+    /// Returns [`Error::AlreadyReturned`] if called again after the data has been returned.
+    pub fn wait_timeout(&mut self, timeout: Duration) -> Result<T> {
+        self.recv(Some(timeout))
+    }
+
+    /// Stops the acquisition and discards its data.
+    pub fn cancel(mut self) -> Result<()> {
+        self.cancel_and_join()
+    }
+
+    fn recv(&mut self, timeout: Option<Duration>) -> Result<T> {
+        let rx = self.rx.as_ref().ok_or(Error::AlreadyReturned)?;
+        let result = match timeout {
+            Some(timeout) => rx.recv_timeout(timeout),
+            None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+        };
+        match result {
+            Ok(result) => {
+                self.rx = None;
+                self.join()?;
+                result
+            }
+            Err(RecvTimeoutError::Timeout) => Err(Error::Timeout),
+            Err(RecvTimeoutError::Disconnected) => {
+                self.rx = None;
+                self.join()?;
+                Err(Error::ThreadPanicked)
+            }
+        }
+    }
+
+    fn join(&mut self) -> Result<()> {
+        match self.worker.take() {
+            Some(worker) => worker.join().map_err(|_| Error::ThreadPanicked),
+            None => Ok(()),
+        }
+    }
+
+    fn cancel_and_join(&mut self) -> Result<()> {
+        self.signal.cancel();
+        let stopped = (self.stop)();
+        self.join()?;
+        stopped
+    }
+}
+
+impl<T> Drop for AcquisitionHandle<T> {
+    fn drop(&mut self) {
+        if !self.is_ready() {
+            let _ = self.cancel_and_join();
+        }
+    }
+}
@@ -0,0 +1,174 @@
+/*
+ * File: asynchronous.rs
+ * Project: src
+ * Created Date: 18/10/2026
+ * Author: Shun Suzuki
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: Shun Suzuki (suzuki@hapis.k.u-tokyo.ac.jp)
+ * -----
+ * Copyright (c) 2026 Shun Suzuki. All rights reserved.
+ *
+ */
+
+use std::{
+    future::Future,
+    panic::{catch_unwind, AssertUnwindSafe},
+    pin::Pin,
+    sync::{
+        mpsc::{self, Receiver},
+        Arc,
+    },
+    task::{Context, Poll},
+};
+
+use futures_core::Stream;
+use pico_common::PicoStatus;
+
+use crate::{
+    acquisition::ReadySignal, block_data::BlockData, driver::Ps4000Driver, ps4000::Ps4000Device,
+    trigger::TriggerConfig, Error, Result,
+};
+
+type Retrieve = Box<dyn FnOnce() -> Result<BlockData> + Send>;
+
+enum BlockState {
+    Capturing(Retrieve),
+    // the data is read on a short-lived thread, which signals `done` when the result is sent
+    Retrieving(Arc<ReadySignal>, Receiver<Result<BlockData>>),
+    Done,
+}
+
+/// A block capture completed by the driver's ready callback, returned by [`Ps4000Device::collect_block_triggered_async`].
+///
+/// The task is woken from the driver thread when the capture completes,
+/// and the data is then read on a short-lived thread so that the USB transfer does not block the executor.
+/// It completes with [`Error::DeviceClosed`] if the device is closed first.
+/// Dropping the future before the capture completes stops the capture.
+pub struct BlockFuture {
+    signal: Arc<ReadySignal>,
+    state: BlockState,
+    stop: Box<dyn Fn() -> Result<()> + Send>,
+}
+
+impl Future for BlockFuture {
+    type Output = Result<BlockData>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        if let BlockState::Capturing(_) = this.state {
+            if let Err(e) = std::task::ready!(this.signal.poll(cx)) {
+                this.state = BlockState::Done;
+                return Poll::Ready(Err(e));
+            }
+            let BlockState::Capturing(retrieve) =
+                std::mem::replace(&mut this.state, BlockState::Done)
+            else {
+                unreachable!()
+            };
+            let done = Arc::new(ReadySignal::default());
+            let (tx, rx) = mpsc::channel();
+            let ready = done.callback();
+            std::thread::spawn(move || {
+                let data =
+                    catch_unwind(AssertUnwindSafe(retrieve)).unwrap_or(Err(Error::ThreadPanicked));
+                let _ = tx.send(data);
+                ready(PicoStatus::OK);
+            });
+            this.state = BlockState::Retrieving(done, rx);
+        }
+        match &this.state {
+            BlockState::Retrieving(done, rx) => {
+                std::task::ready!(done.poll(cx))?;
+                let data = rx.recv().unwrap_or(Err(Error::ThreadPanicked));
+                this.state = BlockState::Done;
+                Poll::Ready(data)
+            }
+            _ => Poll::Ready(Err(Error::AlreadyReturned)),
+        }
+    }
+}
+
+impl Drop for BlockFuture {
+    fn drop(&mut self) {
+        // the driver must stop writing before the buffers owned by `retrieve` are freed
+        if let BlockState::Capturing(_) = self.state {
+            let _ = (self.stop)();
+        }
+    }
+}
+
+/// Repeated triggered block captures, returned by [`Ps4000Device::block_stream`].
+///
+/// The next capture is armed when the stream is polled after yielding a block, and the stream never ends.
+pub struct BlockStream<'a, D: Ps4000Driver + 'static> {
+    device: &'a Ps4000Device<D>,
+    sample_count: u32,
+    sample_rate: u32,
+    cond: TriggerConfig,
+    pending: Option<BlockFuture>,
+}
+
+impl<D: Ps4000Driver + 'static> Stream for BlockStream<'_, D> {
+    type Item = Result<BlockData>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let pending = match &mut this.pending {
+            Some(pending) => pending,
+            None => match this.device.collect_block_triggered_async(
+                this.sample_count,
+                this.sample_rate,
+                this.cond.clone(),
+            ) {
+                Ok(pending) => this.pending.insert(pending),
+                Err(e) => return Poll::Ready(Some(Err(e))),
+            },
+        };
+        match Pin::new(pending).poll(cx) {
+            Poll::Ready(block) => {
+                this.pending = None;
+                Poll::Ready(Some(block))
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl<D: Ps4000Driver + 'static> Ps4000Device<D> {
+    /// Starts a triggered block capture whose data is awaited instead of waited for on a thread.
+    pub fn collect_block_triggered_async(
+        &self,
+        sample_count: u32,
+        sample_rate: u32,
+        cond: impl Into<TriggerConfig>,
+    ) -> Result<BlockFuture> {
+        let cond = cond.into();
+        self.update_channels()?;
+        let timebase = self.capture_timebase(sample_rate, sample_count)?;
+        let signal = self.ready_signal();
+        let (retrieve, stop) =
+            self.start_block(sample_count, timebase, Some(&cond), signal.callback())?;
+        Ok(BlockFuture {
+            signal,
+            state: BlockState::Capturing(Box::new(retrieve)),
+            stop: Box::new(stop),
+        })
+    }
+
+    /// Captures triggered blocks one after another as a [`Stream`].
+    pub fn block_stream(
+        &self,
+        sample_count: u32,
+        sample_rate: u32,
+        cond: impl Into<TriggerConfig>,
+    ) -> BlockStream<'_, D> {
+        BlockStream {
+            device: self,
+            sample_count,
+            sample_rate,
+            cond: cond.into(),
+            pending: None,
+        }
+    }
+}
@@ -4,74 +4,198 @@
  * Created Date: 14/11/2023
  * Author: Shun Suzuki
  * -----
- * Last Modified: 16/11/2023
+ * Last Modified: 18/10/2026
  * Modified By: Shun Suzuki (suzuki@hapis.k.u-tokyo.ac.jp)
  * -----
  * Copyright (c) 2023 Shun Suzuki. All rights reserved.
  *
  */
 
-use std::collections::HashMap;
+use std::time::Duration;
 
 use pico_sys_dynamic::ps4000::PS4000_CHANNEL;
 
-use crate::{attenuation::Attenuation, ps4000::PS4262, range::Range, Channel};
+use crate::{
+    attenuation::Attenuation, buffer_pool::BufferPool, downsampling::Downsampling,
+    variant::convert_adc_to_mv, Channel,
+};
 
+// An enabled channel of a capture and the scale of its samples.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct BlockChannel {
+    pub(crate) channel: PS4000_CHANNEL,
+    pub(crate) range_mv: i32,
+    pub(crate) attenuation: Attenuation,
+}
+
+/// Samples of a block capture.
+///
+/// The samples of all enabled channels share one contiguous buffer, written by the driver in place.
 pub struct BlockData {
     sample_count: usize,
+    // length of each channel's region in the buffers
+    stride: usize,
     overflow: bool,
     time_interval: i32,
-    _min_pinned: HashMap<PS4000_CHANNEL, Vec<i16>>,
-    max_pinned: HashMap<PS4000_CHANNEL, Vec<i16>>,
-    range: HashMap<PS4000_CHANNEL, Range>,
-    attenuation: HashMap<PS4000_CHANNEL, Attenuation>,
+    channels: Vec<BlockChannel>,
+    max: Vec<i16>,
+    // empty unless aggregated
+    min: Vec<i16>,
+    trigger_time_offset: Option<f64>,
+    max_value: u32,
+    downsampling: Downsampling,
+    pool: Option<BufferPool>,
 }
 
 impl BlockData {
-    pub fn new(
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
         sample_count: u32,
+        stride: usize,
         overflow: i16,
         time_interval: i32,
-        min_pinned: HashMap<PS4000_CHANNEL, Vec<i16>>,
-        max_pinned: HashMap<PS4000_CHANNEL, Vec<i16>>,
-        range: HashMap<PS4000_CHANNEL, Range>,
-        attenuation: HashMap<PS4000_CHANNEL, Attenuation>,
+        channels: Vec<BlockChannel>,
+        max: Vec<i16>,
+        min: Vec<i16>,
+        max_value: u32,
     ) -> Self {
         Self {
-            sample_count: sample_count as usize,
+            sample_count: (sample_count as usize).min(stride),
+            stride,
             overflow: overflow != 0,
             time_interval,
-            _min_pinned: min_pinned,
-            max_pinned,
-            range,
-            attenuation,
+            channels,
+            max,
+            min,
+            trigger_time_offset: None,
+            max_value,
+            downsampling: Downsampling::None,
+            pool: None,
         }
     }
 
-    pub fn time_interval(&self) -> std::time::Duration {
-        std::time::Duration::from_nanos(self.time_interval as _)
+    pub(crate) fn with_trigger_time_offset(mut self, trigger_time_offset: f64) -> Self {
+        self.trigger_time_offset = Some(trigger_time_offset);
+        self
+    }
+
+    pub(crate) fn with_downsampling(mut self, downsampling: Downsampling) -> Self {
+        self.downsampling = downsampling;
+        self
+    }
+
+    pub(crate) fn with_pool(mut self, pool: Option<BufferPool>) -> Self {
+        self.pool = pool;
+        self
+    }
+
+    /// Time between two values, which spans the downsampling ratio.
+    pub fn time_interval(&self) -> Duration {
+        Duration::from_nanos(self.time_interval as u64 * self.downsampling.ratio() as u64)
+    }
+
+    pub fn downsampling(&self) -> Downsampling {
+        self.downsampling
     }
 
     pub fn overflow(&self) -> bool {
         self.overflow
     }
 
-    pub fn get_adc(&self, channel: Channel) -> Vec<i16> {
-        self.max_pinned[&channel.into()]
-            .iter()
-            .take(self.sample_count)
-            .copied()
-            .collect()
+    /// Number of values per channel.
+    pub fn len(&self) -> usize {
+        self.sample_count
     }
 
-    pub fn get_mv(&self, channel: Channel) -> Vec<f64> {
-        let channel = channel.into();
-        let range = self.range[&channel];
-        let atten = self.attenuation[&channel];
-        self.max_pinned[&channel]
-            .iter()
-            .take(self.sample_count)
-            .map(|&x| PS4262::convert_adc_to_mv(x, atten, range))
-            .collect()
+    pub fn is_empty(&self) -> bool {
+        self.sample_count == 0
+    }
+
+    /// Time in seconds between the trigger point and the nearest sample, if reported by the driver.
+    pub fn trigger_time_offset(&self) -> Option<f64> {
+        self.trigger_time_offset
+    }
+
+    /// Returns the samples, or the maximum of each group with [`Downsampling::Aggregate`].
+    ///
+    /// The slice is empty if `channel` was not enabled.
+    pub fn get_adc(&self, channel: Channel) -> &[i16] {
+        self.get_max_adc(channel)
+    }
+
+    /// Returns the maximum of each group with [`Downsampling::Aggregate`], otherwise the same as [`BlockData::get_adc`].
+    pub fn get_max_adc(&self, channel: Channel) -> &[i16] {
+        self.region(&self.max, channel)
+            .map_or(&[], |(_, samples)| samples)
+    }
+
+    /// Returns the minimum of each group with [`Downsampling::Aggregate`], otherwise the same as [`BlockData::get_adc`].
+    pub fn get_min_adc(&self, channel: Channel) -> &[i16] {
+        self.region(self.min_buffer(), channel)
+            .map_or(&[], |(_, samples)| samples)
+    }
+
+    /// Converts [`BlockData::get_adc`] to millivolts as it is iterated.
+    pub fn get_mv(&self, channel: Channel) -> impl Iterator<Item = f64> + '_ {
+        self.get_max_mv(channel)
+    }
+
+    /// Millivolt counterpart of [`BlockData::get_max_adc`].
+    pub fn get_max_mv(&self, channel: Channel) -> impl Iterator<Item = f64> + '_ {
+        self.to_mv(&self.max, channel)
+    }
+
+    /// Millivolt counterpart of [`BlockData::get_min_adc`].
+    pub fn get_min_mv(&self, channel: Channel) -> impl Iterator<Item = f64> + '_ {
+        self.to_mv(self.min_buffer(), channel)
+    }
+
+    /// Returns the minimum and maximum of each group in millivolts, for plotting an aggregated capture as an envelope.
+    pub fn envelope(
+        &self,
+        channel: Channel,
+    ) -> (
+        impl Iterator<Item = f64> + '_,
+        impl Iterator<Item = f64> + '_,
+    ) {
+        (self.get_min_mv(channel), self.get_max_mv(channel))
+    }
+
+    // Without aggregation, the driver writes only the max buffer.
+    fn min_buffer(&self) -> &[i16] {
+        match self.downsampling {
+            Downsampling::Aggregate(_) => &self.min,
+            _ => &self.max,
+        }
+    }
+
+    fn region<'a>(&self, buffer: &'a [i16], channel: Channel) -> Option<(BlockChannel, &'a [i16])> {
+        let channel: PS4000_CHANNEL = channel.into();
+        let index = self.channels.iter().position(|ch| ch.channel == channel)?;
+        let start = index * self.stride;
+        Some((
+            self.channels[index],
+            &buffer[start..start + self.sample_count],
+        ))
+    }
+
+    fn to_mv<'a>(&self, buffer: &'a [i16], channel: Channel) -> impl Iterator<Item = f64> + 'a {
+        let max_value = self.max_value;
+        self.region(buffer, channel)
+            .into_iter()
+            .flat_map(move |(ch, samples)| {
+                samples
+                    .iter()
+                    .map(move |&x| convert_adc_to_mv(x, max_value, ch.attenuation, ch.range_mv))
+            })
+    }
+}
+
+impl Drop for BlockData {
+    fn drop(&mut self) {
+        if let Some(pool) = &self.pool {
+            pool.put(std::mem::take(&mut self.max));
+            pool.put(std::mem::take(&mut self.min));
+        }
     }
 }
@@ -0,0 +1,61 @@
+/*
+ * File: buffer_pool.rs
+ * Project: src
+ * Created Date: 18/10/2026
+ * Author: Shun Suzuki
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: Shun Suzuki (suzuki@hapis.k.u-tokyo.ac.jp)
+ * -----
+ * Copyright (c) 2026 Shun Suzuki. All rights reserved.
+ *
+ */
+
+use std::sync::{Arc, Mutex};
+
+/// Sample buffers recycled between block captures, set with [`crate::Ps4000Device::set_buffer_pool`].
+///
+/// A [`crate::BlockData`] returns its buffers to the pool when dropped,
+/// so that repeated captures of the same size do not allocate.
+/// Clones share the same buffers.
+#[derive(Clone, Default)]
+pub struct BufferPool {
+    buffers: Arc<Mutex<Vec<Vec<i16>>>>,
+}
+
+impl BufferPool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of buffers waiting to be reused.
+    pub fn available(&self) -> usize {
+        self.buffers.lock().unwrap().len()
+    }
+
+    // Takes a zeroed buffer of `len` samples, preferring one that does not need to grow.
+    pub(crate) fn take(&self, len: usize) -> Vec<i16> {
+        let mut buffers = self.buffers.lock().unwrap();
+        let mut buffer = match buffers.iter().position(|b| b.capacity() >= len) {
+            Some(i) => buffers.swap_remove(i),
+            None => buffers.pop().unwrap_or_default(),
+        };
+        drop(buffers);
+        buffer.clear();
+        buffer.resize(len, 0);
+        buffer
+    }
+
+    pub(crate) fn put(&self, buffer: Vec<i16>) {
+        if buffer.capacity() > 0 {
+            self.buffers.lock().unwrap().push(buffer);
+        }
+    }
+}
+
+pub(crate) fn alloc(pool: Option<&BufferPool>, len: usize) -> Vec<i16> {
+    match pool {
+        Some(pool) => pool.take(len),
+        None => vec![0; len],
+    }
+}
@@ -0,0 +1,150 @@
+/*
+ * File: captured_block.rs
+ * Project: src
+ * Created Date: 18/10/2026
+ * Author: Shun Suzuki
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: Shun Suzuki (suzuki@hapis.k.u-tokyo.ac.jp)
+ * -----
+ * Copyright (c) 2026 Shun Suzuki. All rights reserved.
+ *
+ */
+
+use std::{ops::Range, sync::Arc, time::Duration};
+
+use pico_sys_dynamic::ps4000::PS4000_CHANNEL;
+
+use crate::{
+    block_data::{BlockChannel, BlockData},
+    buffer_pool::{self, BufferPool},
+    downsampling::Downsampling,
+    driver::Ps4000Driver,
+    ps4000::Ps4000Device,
+    Error, Result,
+};
+
+// Reads a completed block capture out of the device's memory.
+pub(crate) struct BlockReader<D: Ps4000Driver + 'static> {
+    pub(crate) driver: Arc<D>,
+    pub(crate) handle: i16,
+    pub(crate) sample_count: u32,
+    pub(crate) time_interval_ns: i32,
+    pub(crate) enabled: Vec<BlockChannel>,
+    pub(crate) disabled: Vec<PS4000_CHANNEL>,
+    pub(crate) max_value: u32,
+    pub(crate) pool: Option<BufferPool>,
+}
+
+impl<D: Ps4000Driver + 'static> BlockReader<D> {
+    pub(crate) fn read(
+        &self,
+        start_index: u32,
+        no_of_samples: u32,
+        downsampling: Downsampling,
+    ) -> Result<BlockData> {
+        let mode = downsampling.into();
+        let ratio = downsampling.ratio();
+        if downsampling != Downsampling::None {
+            let max_ratio =
+                self.driver
+                    .get_max_down_sample_ratio(self.handle, no_of_samples, mode, 0)?;
+            if ratio > max_ratio {
+                return Err(Error::InvalidConfig(format!(
+                    "downsampling ratio {} exceeds the maximum of {}",
+                    ratio, max_ratio
+                )));
+            }
+        }
+
+        // the driver writes into the buffers only during ps4000GetValues
+        let stride = no_of_samples.div_ceil(ratio) as usize;
+        let len = stride * self.enabled.len();
+        let mut max = buffer_pool::alloc(self.pool.as_ref(), len);
+        let mut min = match downsampling {
+            Downsampling::Aggregate(_) => buffer_pool::alloc(self.pool.as_ref(), len),
+            _ => Vec::new(),
+        };
+        for &channel in self.disabled.iter() {
+            unsafe {
+                self.driver.set_data_buffers(
+                    self.handle,
+                    channel,
+                    std::ptr::null_mut(),
+                    std::ptr::null_mut(),
+                    0,
+                )?;
+            }
+        }
+        for (i, ch) in self.enabled.iter().enumerate() {
+            unsafe {
+                self.driver.set_data_buffers(
+                    self.handle,
+                    ch.channel,
+                    max.as_mut_ptr().add(i * stride),
+                    if min.is_empty() {
+                        std::ptr::null_mut()
+                    } else {
+                        min.as_mut_ptr().add(i * stride)
+                    },
+                    stride as i32,
+                )?;
+            }
+        }
+        let (retrieved, overflow) =
+            self.driver
+                .get_values(self.handle, start_index, no_of_samples, ratio, mode, 0)?;
+
+        Ok(BlockData::new(
+            retrieved,
+            stride,
+            overflow,
+            self.time_interval_ns,
+            self.enabled.clone(),
+            max,
+            min,
+            self.max_value,
+        )
+        .with_downsampling(downsampling)
+        .with_pool(self.pool.clone()))
+    }
+}
+
+/// A completed block capture kept in the device's memory, returned by [`Ps4000Device::capture_block`]
+/// and [`Ps4000Device::capture_block_triggered`].
+///
+/// Parts of it can be read as often as needed with different downsampling, so that the whole record need not be transferred.
+/// The device stays borrowed so that no other capture overwrites the memory.
+pub struct CapturedBlock<'a, D: Ps4000Driver + 'static> {
+    pub(crate) reader: BlockReader<D>,
+    pub(crate) _device: &'a mut Ps4000Device<D>,
+}
+
+impl<D: Ps4000Driver + 'static> CapturedBlock<'_, D> {
+    /// Number of samples captured per channel.
+    pub fn sample_count(&self) -> u32 {
+        self.reader.sample_count
+    }
+
+    /// Time between two captured samples.
+    pub fn time_interval(&self) -> Duration {
+        Duration::from_nanos(self.reader.time_interval_ns as _)
+    }
+
+    /// Reads the `samples` of the capture, downsampled by `downsampling`.
+    pub fn read(&self, samples: Range<u32>, downsampling: Downsampling) -> Result<BlockData> {
+        if samples.is_empty() || samples.end > self.reader.sample_count {
+            return Err(Error::InvalidConfig(format!(
+                "samples {:?} are not within the {} captured",
+                samples, self.reader.sample_count
+            )));
+        }
+        if downsampling.ratio() == 0 {
+            return Err(Error::InvalidConfig(
+                "downsampling ratio must be at least 1".to_string(),
+            ));
+        }
+        self.reader
+            .read(samples.start, samples.end - samples.start, downsampling)
+    }
+}
@@ -4,24 +4,33 @@
  * Created Date: 14/11/2023
  * Author: Shun Suzuki
  * -----
- * Last Modified: 16/11/2023
+ * Last Modified: 18/10/2026
  * Modified By: Shun Suzuki (suzuki@hapis.k.u-tokyo.ac.jp)
  * -----
  * Copyright (c) 2023 Shun Suzuki. All rights reserved.
  *
  */
 
-use pico_common::{PicoCoupling, PicoStatus};
+use pico_common::PicoCoupling;
 use pico_sys_dynamic::ps4000::{
-    enPS4000Channel_PS4000_CHANNEL_A, enPS4000Channel_PS4000_CHANNEL_B, PS4000_CHANNEL,
+    enPS4000Channel_PS4000_CHANNEL_A, enPS4000Channel_PS4000_CHANNEL_B,
+    enPS4000Channel_PS4000_CHANNEL_C, enPS4000Channel_PS4000_CHANNEL_D, PS4000_CHANNEL,
 };
 
-use crate::{attenuation::Attenuation, check_pico_status, range::Range, LIBRARY};
+use crate::{attenuation::Attenuation, driver::Ps4000Driver, range::Range, Result};
 
+/// An analog input.
+///
+/// Indexing a [`crate::Ps4000Device`] with a channel its model lacks panics,
+/// while [`crate::Ps4000Device::channel`] and [`crate::Ps4000Device::channel_mut`] return an error.
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Channel {
     A,
     B,
+    /// Only on 4-channel models.
+    C,
+    /// Only on 4-channel models.
+    D,
 }
 
 impl From<Channel> for PS4000_CHANNEL {
@@ -29,6 +38,8 @@ impl From<Channel> for PS4000_CHANNEL {
         match value {
             Channel::A => enPS4000Channel_PS4000_CHANNEL_A,
             Channel::B => enPS4000Channel_PS4000_CHANNEL_B,
+            Channel::C => enPS4000Channel_PS4000_CHANNEL_C,
+            Channel::D => enPS4000Channel_PS4000_CHANNEL_D,
         }
     }
 }
@@ -44,18 +55,14 @@ pub struct ChannelConfig {
 }
 
 impl ChannelConfig {
-    pub(crate) fn update(&self) -> Result<(), PicoStatus> {
-        unsafe {
-            let loader = LIBRARY.get().unwrap();
-            check_pico_status!(loader.ps4000SetChannel(
-                self.handle,
-                self.channel,
-                if self.enable { 1 } else { 0 },
-                self.coupling.into(),
-                self.range.into_range(self.attenuation)?,
-            ));
-            Ok(())
-        }
+    pub(crate) fn update<D: Ps4000Driver>(&self, driver: &D) -> Result<()> {
+        driver.set_channel(
+            self.handle,
+            self.channel,
+            self.enable,
+            self.coupling,
+            self.range.into_range(self.attenuation)?,
+        )
     }
 
     pub(crate) fn new(channel: PS4000_CHANNEL, handle: i16) -> Self {
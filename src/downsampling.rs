@@ -0,0 +1,50 @@
+/*
+ * File: downsampling.rs
+ * Project: src
+ * Created Date: 18/10/2026
+ * Author: Shun Suzuki
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: Shun Suzuki (suzuki@hapis.k.u-tokyo.ac.jp)
+ * -----
+ * Copyright (c) 2026 Shun Suzuki. All rights reserved.
+ *
+ */
+
+use pico_sys_dynamic::ps4000::{
+    enRatioMode_RATIO_MODE_AGGREGATE, enRatioMode_RATIO_MODE_AVERAGE, enRatioMode_RATIO_MODE_NONE,
+    RATIO_MODE,
+};
+
+/// Downsampling applied by the driver when a block is retrieved, so that fewer samples are transferred over USB.
+///
+/// The ps4000 driver has no decimation mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Downsampling {
+    #[default]
+    None,
+    /// Minimum and maximum of each group of `ratio` samples.
+    Aggregate(u32),
+    /// Average of each group of `ratio` samples.
+    Average(u32),
+}
+
+impl Downsampling {
+    /// Number of samples combined into one value.
+    pub fn ratio(&self) -> u32 {
+        match self {
+            Downsampling::None => 1,
+            Downsampling::Aggregate(ratio) | Downsampling::Average(ratio) => *ratio,
+        }
+    }
+}
+
+impl From<Downsampling> for RATIO_MODE {
+    fn from(value: Downsampling) -> Self {
+        match value {
+            Downsampling::None => enRatioMode_RATIO_MODE_NONE,
+            Downsampling::Aggregate(_) => enRatioMode_RATIO_MODE_AGGREGATE,
+            Downsampling::Average(_) => enRatioMode_RATIO_MODE_AVERAGE,
+        }
+    }
+}
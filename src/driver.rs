@@ -0,0 +1,1189 @@
+/*
+ * File: driver.rs
+ * Project: src
+ * Created Date: 18/10/2026
+ * Author: Shun Suzuki
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: Shun Suzuki (suzuki@hapis.k.u-tokyo.ac.jp)
+ * -----
+ * Copyright (c) 2026 Shun Suzuki. All rights reserved.
+ *
+ */
+
+use std::{
+    ffi::{c_void, OsStr},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+use pico_common::{PicoCoupling, PicoInfo, PicoStatus};
+use pico_sys_dynamic::ps4000::{
+    PS4000Loader, INDEX_MODE, PS4000_CHANNEL, PS4000_OPERATION_TYPES, PS4000_RANGE,
+    PS4000_TIME_UNITS, PULSE_WIDTH_TYPE, PWQ_CONDITIONS, RATIO_MODE, SIGGEN_TRIG_SOURCE,
+    SIGGEN_TRIG_TYPE, SWEEP_TYPE, THRESHOLD_DIRECTION, TRIGGER_CHANNEL_PROPERTIES,
+    TRIGGER_CONDITIONS, WAVE_TYPE,
+};
+
+use crate::{check_pico_status, error::Error, Result};
+
+/// Backend of the ps4000 driver API used by [`crate::Ps4000Device`].
+///
+/// [`Ps4000Library`] forwards every call to the vendor library.
+/// Other implementations can stand in for the library and the hardware.
+pub trait Ps4000Driver: Send + Sync {
+    fn open_unit(&self) -> Result<i16>;
+
+    fn close_unit(&self, handle: i16) -> Result<()>;
+
+    /// Returns the serial numbers of the units that are not opened yet.
+    fn enumerate_units(&self) -> Result<Vec<String>>;
+
+    fn open_unit_ex(&self, serial: &str) -> Result<i16>;
+
+    /// Starts opening the first unit found without waiting for it.
+    fn open_unit_async(&self) -> Result<()>;
+
+    /// Returns the handle, the progress in percent and whether opening started by [`Ps4000Driver::open_unit_async`] is complete.
+    fn open_unit_progress(&self) -> Result<(i16, i16, bool)>;
+
+    fn get_unit_info(&self, handle: i16, info: PicoInfo) -> Result<String>;
+
+    fn set_channel(
+        &self,
+        handle: i16,
+        channel: PS4000_CHANNEL,
+        enabled: bool,
+        coupling: PicoCoupling,
+        range: PS4000_RANGE,
+    ) -> Result<()>;
+
+    #[allow(clippy::too_many_arguments)]
+    fn set_simple_trigger(
+        &self,
+        handle: i16,
+        enable: bool,
+        source: PS4000_CHANNEL,
+        threshold: i16,
+        direction: THRESHOLD_DIRECTION,
+        delay: u32,
+        auto_trigger_ms: i16,
+    ) -> Result<()>;
+
+    fn set_trigger_channel_conditions(
+        &self,
+        handle: i16,
+        conditions: &[TRIGGER_CONDITIONS],
+    ) -> Result<()>;
+
+    #[allow(clippy::too_many_arguments)]
+    fn set_trigger_channel_directions(
+        &self,
+        handle: i16,
+        channel_a: THRESHOLD_DIRECTION,
+        channel_b: THRESHOLD_DIRECTION,
+        channel_c: THRESHOLD_DIRECTION,
+        channel_d: THRESHOLD_DIRECTION,
+        ext: THRESHOLD_DIRECTION,
+        aux: THRESHOLD_DIRECTION,
+    ) -> Result<()>;
+
+    fn set_trigger_channel_properties(
+        &self,
+        handle: i16,
+        properties: &[TRIGGER_CHANNEL_PROPERTIES],
+        aux_output_enable: bool,
+        auto_trigger_ms: i32,
+    ) -> Result<()>;
+
+    fn set_trigger_delay(&self, handle: i16, delay: u32) -> Result<()>;
+
+    /// `lower` and `upper` are given in samples.
+    fn set_pulse_width_qualifier(
+        &self,
+        handle: i16,
+        conditions: &[PWQ_CONDITIONS],
+        direction: THRESHOLD_DIRECTION,
+        lower: u32,
+        upper: u32,
+        ty: PULSE_WIDTH_TYPE,
+    ) -> Result<()>;
+
+    /// `offset_voltage` and `pk_to_pk` are given in microvolts.
+    #[allow(clippy::too_many_arguments)]
+    fn set_sig_gen_built_in(
+        &self,
+        handle: i16,
+        offset_voltage: i32,
+        pk_to_pk: u32,
+        wave_type: WAVE_TYPE,
+        start_frequency: f32,
+        stop_frequency: f32,
+        increment: f32,
+        dwell_time: f32,
+        sweep_type: SWEEP_TYPE,
+        operation: PS4000_OPERATION_TYPES,
+        shots: u32,
+        sweeps: u32,
+        trigger_type: SIGGEN_TRIG_TYPE,
+        trigger_source: SIGGEN_TRIG_SOURCE,
+        ext_in_threshold: i16,
+    ) -> Result<()>;
+
+    fn sig_gen_software_control(&self, handle: i16, state: bool) -> Result<()>;
+
+    fn sig_gen_off(&self, handle: i16) -> Result<()>;
+
+    /// `offset_voltage` and `pk_to_pk` are given in microvolts.
+    #[allow(clippy::too_many_arguments)]
+    fn set_sig_gen_arbitrary(
+        &self,
+        handle: i16,
+        offset_voltage: i32,
+        pk_to_pk: u32,
+        start_delta_phase: u32,
+        stop_delta_phase: u32,
+        delta_phase_increment: u32,
+        dwell_count: u32,
+        waveform: &[i16],
+        sweep_type: SWEEP_TYPE,
+        operation: PS4000_OPERATION_TYPES,
+        index_mode: INDEX_MODE,
+        shots: u32,
+        sweeps: u32,
+        trigger_type: SIGGEN_TRIG_TYPE,
+        trigger_source: SIGGEN_TRIG_SOURCE,
+        ext_in_threshold: i16,
+    ) -> Result<()>;
+
+    /// Returns the delta phase that repeats a waveform of `buffer_length` samples at `frequency`.
+    fn sig_gen_frequency_to_phase(
+        &self,
+        handle: i16,
+        frequency: f64,
+        index_mode: INDEX_MODE,
+        buffer_length: u32,
+    ) -> Result<u32>;
+
+    /// Returns the minimum and maximum sample values and the minimum and maximum length of an arbitrary waveform.
+    fn sig_gen_arbitrary_min_max_values(&self, handle: i16) -> Result<(i16, i16, u32, u32)>;
+
+    /// # Safety
+    ///
+    /// `buffer_max` and `buffer_min` must be valid for writes of `buffer_len` samples
+    /// until the data has been retrieved by [`Ps4000Driver::get_values`] or the buffers are replaced.
+    unsafe fn set_data_buffers(
+        &self,
+        handle: i16,
+        channel: PS4000_CHANNEL,
+        buffer_max: *mut i16,
+        buffer_min: *mut i16,
+        buffer_len: i32,
+    ) -> Result<()>;
+
+    /// Returns the sampling interval in nanoseconds and the maximum number of samples.
+    fn get_timebase(
+        &self,
+        handle: i16,
+        timebase: u32,
+        no_of_samples: i32,
+        oversample: i16,
+        segment_index: u16,
+    ) -> Result<(i32, i32)>;
+
+    /// Returns the time in milliseconds the device will spend collecting samples.
+    fn run_block(
+        &self,
+        handle: i16,
+        no_of_pre_trigger_samples: i32,
+        no_of_post_trigger_samples: i32,
+        timebase: u32,
+        oversample: i16,
+        segment_index: u16,
+    ) -> Result<i32>;
+
+    /// Like [`Ps4000Driver::run_block`], but `ready` is called from a driver thread once the capture completes,
+    /// so that [`Ps4000Driver::is_ready`] need not be polled.
+    ///
+    /// `ready` may never be called if the capture is stopped before it completes,
+    /// in which case it should be dropped by [`Ps4000Driver::stop`], [`Ps4000Driver::close_unit`]
+    /// or the next capture, so that the capture completes with [`Error::Superseded`].
+    #[allow(clippy::too_many_arguments)]
+    fn run_block_with_callback(
+        &self,
+        handle: i16,
+        no_of_pre_trigger_samples: i32,
+        no_of_post_trigger_samples: i32,
+        timebase: u32,
+        oversample: i16,
+        segment_index: u16,
+        ready: BlockReady,
+    ) -> Result<i32>;
+
+    fn is_ready(&self, handle: i16) -> Result<bool>;
+
+    /// Returns the largest downsampling ratio usable for `no_of_unaggregated_samples` samples of the last capture.
+    fn get_max_down_sample_ratio(
+        &self,
+        handle: i16,
+        no_of_unaggregated_samples: u32,
+        down_sample_ratio_mode: RATIO_MODE,
+        segment_index: u16,
+    ) -> Result<u32>;
+
+    /// Returns the number of samples retrieved and the overflow flags.
+    ///
+    /// With downsampling, `no_of_samples` counts the samples before downsampling
+    /// and the number retrieved counts the values written to each buffer.
+    fn get_values(
+        &self,
+        handle: i16,
+        start_index: u32,
+        no_of_samples: u32,
+        down_sample_ratio: u32,
+        down_sample_ratio_mode: RATIO_MODE,
+        segment_index: u16,
+    ) -> Result<(u32, i16)>;
+
+    fn stop(&self, handle: i16) -> Result<()>;
+
+    /// Returns the sampling interval actually used, in `time_units`.
+    #[allow(clippy::too_many_arguments)]
+    fn run_streaming(
+        &self,
+        handle: i16,
+        sample_interval: u32,
+        time_units: PS4000_TIME_UNITS,
+        max_pre_trigger_samples: u32,
+        max_post_trigger_samples: u32,
+        auto_stop: bool,
+        down_sample_ratio: u32,
+        overview_buffer_size: u32,
+    ) -> Result<u32>;
+
+    /// Calls `callback` for each block of samples written to the data buffers since the last call.
+    fn get_streaming_latest_values(
+        &self,
+        handle: i16,
+        callback: &mut dyn FnMut(StreamingReady),
+    ) -> Result<()>;
+
+    /// Returns the maximum number of samples per segment.
+    fn memory_segments(&self, handle: i16, no_of_segments: u16) -> Result<i32>;
+
+    fn set_no_of_captures(&self, handle: i16, no_of_captures: u16) -> Result<()>;
+
+    /// # Safety
+    ///
+    /// `buffer` must be valid for writes of `buffer_len` samples
+    /// until the data has been retrieved by [`Ps4000Driver::get_values_bulk`] or the buffer is replaced.
+    unsafe fn set_data_buffer_bulk(
+        &self,
+        handle: i16,
+        channel: PS4000_CHANNEL,
+        buffer: *mut i16,
+        buffer_len: i32,
+        waveform: u16,
+    ) -> Result<()>;
+
+    /// Returns the number of samples retrieved per segment and writes the overflow flags of each segment to `overflow`.
+    fn get_values_bulk(
+        &self,
+        handle: i16,
+        no_of_samples: u32,
+        from_segment_index: u16,
+        to_segment_index: u16,
+        overflow: &mut [i16],
+    ) -> Result<u32>;
+
+    fn get_values_trigger_time_offset_bulk64(
+        &self,
+        handle: i16,
+        times: &mut [i64],
+        time_units: &mut [PS4000_TIME_UNITS],
+        from_segment_index: u16,
+        to_segment_index: u16,
+    ) -> Result<()>;
+}
+
+/// Callback of [`Ps4000Driver::run_block_with_callback`], called with the status of the capture.
+pub type BlockReady = Box<dyn FnOnce(PicoStatus) + Send>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StreamingReady {
+    pub no_of_samples: i32,
+    pub start_index: u32,
+    pub overflow: i16,
+    pub trigger_at: u32,
+    pub triggered: bool,
+    pub auto_stop: bool,
+}
+
+pub const LIBRARY_PATH_ENV: &str = "PS4000_LIBRARY_PATH";
+
+#[cfg(target_os = "windows")]
+const DEFAULT_LIBRARY_PATHS: &[&str] = &["ps4000.dll"];
+#[cfg(target_os = "macos")]
+const DEFAULT_LIBRARY_PATHS: &[&str] = &[
+    "/Library/Frameworks/PicoSDK.framework/Libraries/libps4000/libps4000.dylib",
+    "libps4000.dylib",
+];
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+const DEFAULT_LIBRARY_PATHS: &[&str] = &["/opt/picoscope/lib/libps4000.so", "libps4000.so"];
+
+pub struct Ps4000Library {
+    loader: PS4000Loader,
+    // identifies the callbacks of this library in `BLOCK_READY`
+    id: usize,
+}
+
+// Callbacks of captures started by `run_block_with_callback`, with the token of their run and their library and handle.
+// The driver is passed only the token, so that a callback it never calls is dropped when the capture is stopped,
+// replaced or the unit closed, and a late call for an earlier run finds nothing.
+static BLOCK_READY: Mutex<Vec<PendingReady>> = Mutex::new(Vec::new());
+
+struct PendingReady {
+    token: usize,
+    unit: (usize, i16),
+    ready: BlockReady,
+}
+
+fn take_block_ready(token: usize) -> Option<BlockReady> {
+    let mut callbacks = BLOCK_READY.lock().unwrap();
+    let index = callbacks.iter().position(|c| c.token == token)?;
+    Some(callbacks.swap_remove(index).ready)
+}
+
+// Drops the callback of the run of `unit`, which the driver will not call.
+fn drop_block_ready(unit: (usize, i16)) {
+    let dropped = {
+        let mut callbacks = BLOCK_READY.lock().unwrap();
+        callbacks
+            .iter()
+            .position(|c| c.unit == unit)
+            .map(|index| callbacks.swap_remove(index))
+    };
+    // dropping completes the capture with an error, so it is done outside the lock
+    drop(dropped);
+}
+
+impl Ps4000Library {
+    /// Loads the library from `PS4000_LIBRARY_PATH` if set, otherwise from the default location of the platform.
+    pub fn new() -> Result<Self> {
+        if let Some(path) = std::env::var_os(LIBRARY_PATH_ENV) {
+            return Self::with_path(path);
+        }
+
+        let mut last_err = None;
+        for path in DEFAULT_LIBRARY_PATHS {
+            match Self::with_path(path) {
+                Ok(library) => return Ok(library),
+                Err(e) => last_err = Some(e),
+            }
+        }
+        Err(last_err.unwrap())
+    }
+
+    pub fn with_path(path: impl AsRef<OsStr>) -> Result<Self> {
+        let path = path.as_ref();
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+        Ok(Self {
+            loader: unsafe { PS4000Loader::new(path) }.map_err(|source| Error::LibraryLoad {
+                path: path.into(),
+                source,
+            })?,
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+        })
+    }
+}
+
+impl Ps4000Driver for Ps4000Library {
+    fn open_unit(&self) -> Result<i16> {
+        let mut handle = -1;
+        unsafe {
+            check_pico_status!(
+                self.loader.ps4000OpenUnit(&mut handle as _),
+                "ps4000OpenUnit"
+            );
+        }
+        Ok(handle)
+    }
+
+    fn close_unit(&self, handle: i16) -> Result<()> {
+        unsafe {
+            check_pico_status!(self.loader.ps4000CloseUnit(handle), "ps4000CloseUnit");
+        }
+        drop_block_ready((self.id, handle));
+        Ok(())
+    }
+
+    fn enumerate_units(&self) -> Result<Vec<String>> {
+        let mut count = 0i16;
+        let mut serials = vec![0i8; 1024];
+        let mut serial_len = serials.len() as i16;
+        unsafe {
+            check_pico_status!(
+                self.loader.ps4000EnumerateUnits(
+                    &mut count as _,
+                    serials.as_mut_ptr(),
+                    &mut serial_len as _,
+                ),
+                "ps4000EnumerateUnits"
+            );
+        }
+        Ok(from_c_string(&serials)
+            .split(',')
+            .filter(|serial| !serial.is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+
+    fn open_unit_ex(&self, serial: &str) -> Result<i16> {
+        let mut serial = serial
+            .bytes()
+            .map(|b| b as i8)
+            .chain(std::iter::once(0))
+            .collect::<Vec<_>>();
+        let mut handle = -1;
+        unsafe {
+            check_pico_status!(
+                self.loader
+                    .ps4000OpenUnitEx(&mut handle as _, serial.as_mut_ptr()),
+                "ps4000OpenUnitEx"
+            );
+        }
+        if handle <= 0 {
+            return Err(Error::driver("ps4000OpenUnitEx", PicoStatus::NOT_FOUND));
+        }
+        Ok(handle)
+    }
+
+    fn open_unit_async(&self) -> Result<()> {
+        let mut status = 0i16;
+        unsafe {
+            check_pico_status!(
+                self.loader.ps4000OpenUnitAsync(&mut status as _),
+                "ps4000OpenUnitAsync"
+            );
+        }
+        if status == 0 {
+            return Err(Error::driver("ps4000OpenUnitAsync", PicoStatus::NOT_FOUND));
+        }
+        Ok(())
+    }
+
+    fn open_unit_progress(&self) -> Result<(i16, i16, bool)> {
+        let mut handle = -1;
+        let mut progress = 0;
+        let mut complete = 0i16;
+        unsafe {
+            check_pico_status!(
+                self.loader.ps4000OpenUnitProgress(
+                    &mut handle as _,
+                    &mut progress as _,
+                    &mut complete as _
+                ),
+                "ps4000OpenUnitProgress"
+            );
+        }
+        Ok((handle, progress, complete != 0))
+    }
+
+    fn get_unit_info(&self, handle: i16, info: PicoInfo) -> Result<String> {
+        let mut buffer = vec![0i8; 64];
+        loop {
+            let mut required = 0i16;
+            unsafe {
+                check_pico_status!(
+                    self.loader.ps4000GetUnitInfo(
+                        handle,
+                        buffer.as_mut_ptr(),
+                        buffer.len() as _,
+                        &mut required as _,
+                        info.into(),
+                    ),
+                    "ps4000GetUnitInfo"
+                );
+            }
+            if (required as usize) <= buffer.len() {
+                return Ok(from_c_string(&buffer));
+            }
+            buffer.resize(required as usize, 0);
+        }
+    }
+
+    fn set_channel(
+        &self,
+        handle: i16,
+        channel: PS4000_CHANNEL,
+        enabled: bool,
+        coupling: PicoCoupling,
+        range: PS4000_RANGE,
+    ) -> Result<()> {
+        unsafe {
+            check_pico_status!(
+                self.loader.ps4000SetChannel(
+                    handle,
+                    channel,
+                    if enabled { 1 } else { 0 },
+                    coupling.into(),
+                    range,
+                ),
+                "ps4000SetChannel"
+            );
+        }
+        Ok(())
+    }
+
+    fn set_simple_trigger(
+        &self,
+        handle: i16,
+        enable: bool,
+        source: PS4000_CHANNEL,
+        threshold: i16,
+        direction: THRESHOLD_DIRECTION,
+        delay: u32,
+        auto_trigger_ms: i16,
+    ) -> Result<()> {
+        unsafe {
+            check_pico_status!(
+                self.loader.ps4000SetSimpleTrigger(
+                    handle,
+                    if enable { 1 } else { 0 },
+                    source,
+                    threshold,
+                    direction,
+                    delay,
+                    auto_trigger_ms,
+                ),
+                "ps4000SetSimpleTrigger"
+            );
+        }
+        Ok(())
+    }
+
+    fn set_trigger_channel_conditions(
+        &self,
+        handle: i16,
+        conditions: &[TRIGGER_CONDITIONS],
+    ) -> Result<()> {
+        let mut conditions = conditions.to_vec();
+        unsafe {
+            check_pico_status!(
+                self.loader.ps4000SetTriggerChannelConditions(
+                    handle,
+                    conditions.as_mut_ptr(),
+                    conditions.len() as _,
+                ),
+                "ps4000SetTriggerChannelConditions"
+            );
+        }
+        Ok(())
+    }
+
+    fn set_trigger_channel_directions(
+        &self,
+        handle: i16,
+        channel_a: THRESHOLD_DIRECTION,
+        channel_b: THRESHOLD_DIRECTION,
+        channel_c: THRESHOLD_DIRECTION,
+        channel_d: THRESHOLD_DIRECTION,
+        ext: THRESHOLD_DIRECTION,
+        aux: THRESHOLD_DIRECTION,
+    ) -> Result<()> {
+        unsafe {
+            check_pico_status!(
+                self.loader.ps4000SetTriggerChannelDirections(
+                    handle, channel_a, channel_b, channel_c, channel_d, ext, aux,
+                ),
+                "ps4000SetTriggerChannelDirections"
+            );
+        }
+        Ok(())
+    }
+
+    fn set_trigger_channel_properties(
+        &self,
+        handle: i16,
+        properties: &[TRIGGER_CHANNEL_PROPERTIES],
+        aux_output_enable: bool,
+        auto_trigger_ms: i32,
+    ) -> Result<()> {
+        let mut properties = properties.to_vec();
+        unsafe {
+            check_pico_status!(
+                self.loader.ps4000SetTriggerChannelProperties(
+                    handle,
+                    properties.as_mut_ptr(),
+                    properties.len() as _,
+                    if aux_output_enable { 1 } else { 0 },
+                    auto_trigger_ms,
+                ),
+                "ps4000SetTriggerChannelProperties"
+            );
+        }
+        Ok(())
+    }
+
+    fn set_trigger_delay(&self, handle: i16, delay: u32) -> Result<()> {
+        unsafe {
+            check_pico_status!(
+                self.loader.ps4000SetTriggerDelay(handle, delay),
+                "ps4000SetTriggerDelay"
+            );
+        }
+        Ok(())
+    }
+
+    fn set_pulse_width_qualifier(
+        &self,
+        handle: i16,
+        conditions: &[PWQ_CONDITIONS],
+        direction: THRESHOLD_DIRECTION,
+        lower: u32,
+        upper: u32,
+        ty: PULSE_WIDTH_TYPE,
+    ) -> Result<()> {
+        let mut conditions = conditions.to_vec();
+        unsafe {
+            check_pico_status!(
+                self.loader.ps4000SetPulseWidthQualifier(
+                    handle,
+                    conditions.as_mut_ptr(),
+                    conditions.len() as _,
+                    direction,
+                    lower,
+                    upper,
+                    ty,
+                ),
+                "ps4000SetPulseWidthQualifier"
+            );
+        }
+        Ok(())
+    }
+
+    fn set_sig_gen_built_in(
+        &self,
+        handle: i16,
+        offset_voltage: i32,
+        pk_to_pk: u32,
+        wave_type: WAVE_TYPE,
+        start_frequency: f32,
+        stop_frequency: f32,
+        increment: f32,
+        dwell_time: f32,
+        sweep_type: SWEEP_TYPE,
+        operation: PS4000_OPERATION_TYPES,
+        shots: u32,
+        sweeps: u32,
+        trigger_type: SIGGEN_TRIG_TYPE,
+        trigger_source: SIGGEN_TRIG_SOURCE,
+        ext_in_threshold: i16,
+    ) -> Result<()> {
+        unsafe {
+            check_pico_status!(
+                self.loader.ps4000SetSigGenBuiltIn(
+                    handle,
+                    offset_voltage,
+                    pk_to_pk,
+                    wave_type as _,
+                    start_frequency,
+                    stop_frequency,
+                    increment,
+                    dwell_time,
+                    sweep_type,
+                    operation as _,
+                    shots,
+                    sweeps,
+                    trigger_type,
+                    trigger_source,
+                    ext_in_threshold,
+                ),
+                "ps4000SetSigGenBuiltIn"
+            );
+        }
+        Ok(())
+    }
+
+    fn sig_gen_software_control(&self, handle: i16, state: bool) -> Result<()> {
+        unsafe {
+            check_pico_status!(
+                self.loader
+                    .ps4000SigGenSoftwareControl(handle, if state { 1 } else { 0 }),
+                "ps4000SigGenSoftwareControl"
+            );
+        }
+        Ok(())
+    }
+
+    fn sig_gen_off(&self, handle: i16) -> Result<()> {
+        unsafe {
+            check_pico_status!(self.loader.ps4000SigGenOff(handle), "ps4000SigGenOff");
+        }
+        Ok(())
+    }
+
+    fn set_sig_gen_arbitrary(
+        &self,
+        handle: i16,
+        offset_voltage: i32,
+        pk_to_pk: u32,
+        start_delta_phase: u32,
+        stop_delta_phase: u32,
+        delta_phase_increment: u32,
+        dwell_count: u32,
+        waveform: &[i16],
+        sweep_type: SWEEP_TYPE,
+        operation: PS4000_OPERATION_TYPES,
+        index_mode: INDEX_MODE,
+        shots: u32,
+        sweeps: u32,
+        trigger_type: SIGGEN_TRIG_TYPE,
+        trigger_source: SIGGEN_TRIG_SOURCE,
+        ext_in_threshold: i16,
+    ) -> Result<()> {
+        let mut waveform = waveform.to_vec();
+        unsafe {
+            check_pico_status!(
+                self.loader.ps4000SetSigGenArbitrary(
+                    handle,
+                    offset_voltage,
+                    pk_to_pk,
+                    start_delta_phase,
+                    stop_delta_phase,
+                    delta_phase_increment,
+                    dwell_count,
+                    waveform.as_mut_ptr(),
+                    waveform.len() as _,
+                    sweep_type,
+                    operation as _,
+                    index_mode,
+                    shots,
+                    sweeps,
+                    trigger_type,
+                    trigger_source,
+                    ext_in_threshold,
+                ),
+                "ps4000SetSigGenArbitrary"
+            );
+        }
+        Ok(())
+    }
+
+    fn sig_gen_frequency_to_phase(
+        &self,
+        handle: i16,
+        frequency: f64,
+        index_mode: INDEX_MODE,
+        buffer_length: u32,
+    ) -> Result<u32> {
+        let mut phase = 0u32;
+        unsafe {
+            check_pico_status!(
+                self.loader.ps4000SigGenFrequencyToPhase(
+                    handle,
+                    frequency,
+                    index_mode,
+                    buffer_length,
+                    &mut phase as _,
+                ),
+                "ps4000SigGenFrequencyToPhase"
+            );
+        }
+        Ok(phase)
+    }
+
+    fn sig_gen_arbitrary_min_max_values(&self, handle: i16) -> Result<(i16, i16, u32, u32)> {
+        let mut min_value = 0i16;
+        let mut max_value = 0i16;
+        let mut min_size = 0u32;
+        let mut max_size = 0u32;
+        unsafe {
+            check_pico_status!(
+                self.loader.ps4000SigGenArbitraryMinMaxValues(
+                    handle,
+                    &mut min_value as _,
+                    &mut max_value as _,
+                    &mut min_size as _,
+                    &mut max_size as _,
+                ),
+                "ps4000SigGenArbitraryMinMaxValues"
+            );
+        }
+        Ok((min_value, max_value, min_size, max_size))
+    }
+
+    unsafe fn set_data_buffers(
+        &self,
+        handle: i16,
+        channel: PS4000_CHANNEL,
+        buffer_max: *mut i16,
+        buffer_min: *mut i16,
+        buffer_len: i32,
+    ) -> Result<()> {
+        check_pico_status!(
+            self.loader
+                .ps4000SetDataBuffers(handle, channel, buffer_max, buffer_min, buffer_len),
+            "ps4000SetDataBuffers"
+        );
+        Ok(())
+    }
+
+    fn get_timebase(
+        &self,
+        handle: i16,
+        timebase: u32,
+        no_of_samples: i32,
+        oversample: i16,
+        segment_index: u16,
+    ) -> Result<(i32, i32)> {
+        let mut time_interval_nanoseconds = 0i32;
+        let mut max_samples = 0i32;
+        unsafe {
+            check_pico_status!(
+                self.loader.ps4000GetTimebase(
+                    handle,
+                    timebase,
+                    no_of_samples,
+                    &mut time_interval_nanoseconds as _,
+                    oversample,
+                    &mut max_samples as _,
+                    segment_index,
+                ),
+                "ps4000GetTimebase"
+            );
+        }
+        Ok((time_interval_nanoseconds, max_samples))
+    }
+
+    fn run_block(
+        &self,
+        handle: i16,
+        no_of_pre_trigger_samples: i32,
+        no_of_post_trigger_samples: i32,
+        timebase: u32,
+        oversample: i16,
+        segment_index: u16,
+    ) -> Result<i32> {
+        let mut time_indisposed_ms = 0i32;
+        unsafe {
+            check_pico_status!(
+                self.loader.ps4000RunBlock(
+                    handle,
+                    no_of_pre_trigger_samples,
+                    no_of_post_trigger_samples,
+                    timebase,
+                    oversample,
+                    &mut time_indisposed_ms as _,
+                    segment_index,
+                    None,
+                    std::ptr::null_mut(),
+                ),
+                "ps4000RunBlock"
+            );
+        }
+        Ok(time_indisposed_ms)
+    }
+
+    fn run_block_with_callback(
+        &self,
+        handle: i16,
+        no_of_pre_trigger_samples: i32,
+        no_of_post_trigger_samples: i32,
+        timebase: u32,
+        oversample: i16,
+        segment_index: u16,
+        ready: BlockReady,
+    ) -> Result<i32> {
+        extern "C" fn block_ready(_handle: i16, status: u32, parameter: *mut c_void) {
+            if let Some(ready) = take_block_ready(parameter as usize) {
+                // unwinding into the driver is undefined behaviour
+                let _ =
+                    std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| ready(status.into())));
+            }
+        }
+
+        // tokens start at 1 so that the parameter is never null
+        static NEXT_TOKEN: AtomicUsize = AtomicUsize::new(1);
+        let token = NEXT_TOKEN.fetch_add(1, Ordering::Relaxed);
+        // replaces the callback of a capture that was never waited for
+        drop_block_ready((self.id, handle));
+        BLOCK_READY.lock().unwrap().push(PendingReady {
+            token,
+            unit: (self.id, handle),
+            ready,
+        });
+        let mut time_indisposed_ms = 0i32;
+        let status: PicoStatus = unsafe {
+            self.loader.ps4000RunBlock(
+                handle,
+                no_of_pre_trigger_samples,
+                no_of_post_trigger_samples,
+                timebase,
+                oversample,
+                &mut time_indisposed_ms as _,
+                segment_index,
+                Some(block_ready),
+                token as *mut c_void,
+            )
+        }
+        .into();
+        if status != PicoStatus::OK {
+            drop(take_block_ready(token));
+            return Err(Error::driver("ps4000RunBlock", status));
+        }
+        Ok(time_indisposed_ms)
+    }
+
+    fn is_ready(&self, handle: i16) -> Result<bool> {
+        let mut ready = 0i16;
+        unsafe {
+            check_pico_status!(
+                self.loader.ps4000IsReady(handle, &mut ready as _),
+                "ps4000IsReady"
+            );
+        }
+        Ok(ready != 0)
+    }
+
+    fn get_max_down_sample_ratio(
+        &self,
+        handle: i16,
+        no_of_unaggregated_samples: u32,
+        down_sample_ratio_mode: RATIO_MODE,
+        segment_index: u16,
+    ) -> Result<u32> {
+        let mut max_down_sample_ratio = 0u32;
+        unsafe {
+            check_pico_status!(
+                self.loader.ps4000GetMaxDownSampleRatio(
+                    handle,
+                    no_of_unaggregated_samples,
+                    &mut max_down_sample_ratio as _,
+                    down_sample_ratio_mode as _,
+                    segment_index,
+                ),
+                "ps4000GetMaxDownSampleRatio"
+            );
+        }
+        Ok(max_down_sample_ratio)
+    }
+
+    fn get_values(
+        &self,
+        handle: i16,
+        start_index: u32,
+        no_of_samples: u32,
+        down_sample_ratio: u32,
+        down_sample_ratio_mode: RATIO_MODE,
+        segment_index: u16,
+    ) -> Result<(u32, i16)> {
+        let mut no_of_samples = no_of_samples;
+        let mut overflow = 0i16;
+        unsafe {
+            check_pico_status!(
+                self.loader.ps4000GetValues(
+                    handle,
+                    start_index,
+                    &mut no_of_samples as _,
+                    down_sample_ratio,
+                    down_sample_ratio_mode as _,
+                    segment_index,
+                    &mut overflow as _,
+                ),
+                "ps4000GetValues"
+            );
+        }
+        Ok((no_of_samples, overflow))
+    }
+
+    fn stop(&self, handle: i16) -> Result<()> {
+        unsafe {
+            check_pico_status!(self.loader.ps4000Stop(handle), "ps4000Stop");
+        }
+        // the driver does not call back for a stopped capture
+        drop_block_ready((self.id, handle));
+        Ok(())
+    }
+
+    fn run_streaming(
+        &self,
+        handle: i16,
+        sample_interval: u32,
+        time_units: PS4000_TIME_UNITS,
+        max_pre_trigger_samples: u32,
+        max_post_trigger_samples: u32,
+        auto_stop: bool,
+        down_sample_ratio: u32,
+        overview_buffer_size: u32,
+    ) -> Result<u32> {
+        let mut sample_interval = sample_interval;
+        unsafe {
+            check_pico_status!(
+                self.loader.ps4000RunStreaming(
+                    handle,
+                    &mut sample_interval as _,
+                    time_units,
+                    max_pre_trigger_samples,
+                    max_post_trigger_samples,
+                    if auto_stop { 1 } else { 0 },
+                    down_sample_ratio,
+                    overview_buffer_size,
+                ),
+                "ps4000RunStreaming"
+            );
+        }
+        Ok(sample_interval)
+    }
+
+    fn get_streaming_latest_values(
+        &self,
+        handle: i16,
+        callback: &mut dyn FnMut(StreamingReady),
+    ) -> Result<()> {
+        unsafe extern "C" fn streaming_ready(
+            _handle: i16,
+            no_of_samples: i32,
+            start_index: u32,
+            overflow: i16,
+            trigger_at: u32,
+            triggered: i16,
+            auto_stop: i16,
+            parameter: *mut c_void,
+        ) {
+            let callback = &mut *(parameter as *mut &mut dyn FnMut(StreamingReady));
+            callback(StreamingReady {
+                no_of_samples,
+                start_index,
+                overflow,
+                trigger_at,
+                triggered: triggered != 0,
+                auto_stop: auto_stop != 0,
+            });
+        }
+
+        let mut callback = callback;
+        unsafe {
+            check_pico_status!(
+                self.loader.ps4000GetStreamingLatestValues(
+                    handle,
+                    Some(streaming_ready),
+                    &mut callback as *mut &mut dyn FnMut(StreamingReady) as *mut c_void,
+                ),
+                "ps4000GetStreamingLatestValues"
+            );
+        }
+        Ok(())
+    }
+
+    fn memory_segments(&self, handle: i16, no_of_segments: u16) -> Result<i32> {
+        let mut max_samples = 0i32;
+        unsafe {
+            check_pico_status!(
+                self.loader
+                    .ps4000MemorySegments(handle, no_of_segments, &mut max_samples as _),
+                "ps4000MemorySegments"
+            );
+        }
+        Ok(max_samples)
+    }
+
+    fn set_no_of_captures(&self, handle: i16, no_of_captures: u16) -> Result<()> {
+        unsafe {
+            check_pico_status!(
+                self.loader.ps4000SetNoOfCaptures(handle, no_of_captures),
+                "ps4000SetNoOfCaptures"
+            );
+        }
+        Ok(())
+    }
+
+    unsafe fn set_data_buffer_bulk(
+        &self,
+        handle: i16,
+        channel: PS4000_CHANNEL,
+        buffer: *mut i16,
+        buffer_len: i32,
+        waveform: u16,
+    ) -> Result<()> {
+        check_pico_status!(
+            self.loader
+                .ps4000SetDataBufferBulk(handle, channel, buffer, buffer_len, waveform),
+            "ps4000SetDataBufferBulk"
+        );
+        Ok(())
+    }
+
+    fn get_values_bulk(
+        &self,
+        handle: i16,
+        no_of_samples: u32,
+        from_segment_index: u16,
+        to_segment_index: u16,
+        overflow: &mut [i16],
+    ) -> Result<u32> {
+        if from_segment_index > to_segment_index
+            || overflow.len() <= (to_segment_index - from_segment_index) as usize
+        {
+            return Err(Error::driver(
+                "ps4000GetValuesBulk",
+                PicoStatus::INVALID_PARAMETER,
+            ));
+        }
+        let mut no_of_samples = no_of_samples;
+        unsafe {
+            check_pico_status!(
+                self.loader.ps4000GetValuesBulk(
+                    handle,
+                    &mut no_of_samples as _,
+                    from_segment_index,
+                    to_segment_index,
+                    overflow.as_mut_ptr(),
+                ),
+                "ps4000GetValuesBulk"
+            );
+        }
+        Ok(no_of_samples)
+    }
+
+    fn get_values_trigger_time_offset_bulk64(
+        &self,
+        handle: i16,
+        times: &mut [i64],
+        time_units: &mut [PS4000_TIME_UNITS],
+        from_segment_index: u16,
+        to_segment_index: u16,
+    ) -> Result<()> {
+        let n = (to_segment_index as usize + 1).saturating_sub(from_segment_index as usize);
+        if n == 0 || times.len() < n || time_units.len() < n {
+            return Err(Error::driver(
+                "ps4000GetValuesTriggerTimeOffsetBulk64",
+                PicoStatus::INVALID_PARAMETER,
+            ));
+        }
+        unsafe {
+            check_pico_status!(
+                self.loader.ps4000GetValuesTriggerTimeOffsetBulk64(
+                    handle,
+                    times.as_mut_ptr(),
+                    time_units.as_mut_ptr(),
+                    from_segment_index,
+                    to_segment_index,
+                ),
+                "ps4000GetValuesTriggerTimeOffsetBulk64"
+            );
+        }
+        Ok(())
+    }
+}
+
+fn from_c_string(buffer: &[i8]) -> String {
+    let bytes = buffer
+        .iter()
+        .take_while(|&&c| c != 0)
+        .map(|&c| c as u8)
+        .collect::<Vec<_>>();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
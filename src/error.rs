@@ -0,0 +1,88 @@
+/*
+ * File: error.rs
+ * Project: src
+ * Created Date: 18/10/2026
+ * Author: Shun Suzuki
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: Shun Suzuki (suzuki@hapis.k.u-tokyo.ac.jp)
+ * -----
+ * Copyright (c) 2026 Shun Suzuki. All rights reserved.
+ *
+ */
+
+use std::{fmt, path::PathBuf};
+
+use pico_common::PicoStatus;
+
+#[derive(Debug)]
+pub enum Error {
+    LibraryLoad {
+        path: PathBuf,
+        source: libloading::Error,
+    },
+    Driver {
+        function: &'static str,
+        status: PicoStatus,
+    },
+    InvalidConfig(String),
+    /// The unit reported a variant this crate does not support, or not the one that was asked for.
+    UnsupportedVariant(String),
+    ThreadPanicked,
+    Timeout,
+    /// The device was closed while an acquisition was in progress.
+    DeviceClosed,
+    /// The result was already returned by an earlier call.
+    AlreadyReturned,
+    /// The capture was stopped or replaced by another capture before it completed.
+    Superseded,
+}
+
+impl Error {
+    pub(crate) fn driver(function: &'static str, status: PicoStatus) -> Self {
+        Self::Driver { function, status }
+    }
+
+    pub fn status(&self) -> Option<PicoStatus> {
+        match self {
+            Self::Driver { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::LibraryLoad { path, .. } => {
+                write!(f, "failed to load driver library {}", path.display())
+            }
+            Self::Driver { function, status } => write!(f, "{} failed: {:?}", function, status),
+            Self::InvalidConfig(msg) => write!(f, "invalid configuration: {}", msg),
+            Self::UnsupportedVariant(variant) => write!(f, "unsupported variant: {}", variant),
+            Self::ThreadPanicked => write!(f, "acquisition thread panicked"),
+            Self::Timeout => write!(f, "operation timed out"),
+            Self::DeviceClosed => write!(f, "device was closed"),
+            Self::AlreadyReturned => write!(f, "result has already been returned"),
+            Self::Superseded => write!(f, "capture was stopped before it completed"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::LibraryLoad { source, .. } => Some(source),
+            Self::Driver { status, .. } => Some(status),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::convert::Infallible> for Error {
+    fn from(value: std::convert::Infallible) -> Self {
+        match value {}
+    }
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
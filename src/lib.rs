@@ -1,27 +1,64 @@
-use std::sync::OnceLock;
-
+mod acquisition;
+#[cfg(feature = "async")]
+mod asynchronous;
 mod attenuation;
 mod block_data;
+mod buffer_pool;
+mod captured_block;
 mod channel;
+mod downsampling;
+mod driver;
+mod error;
 mod ps4000;
 mod range;
+mod session;
+mod sig_gen;
+mod simulated;
+mod stream;
+mod timebase;
 mod trigger;
+mod unit;
+mod variant;
 
-static LIBRARY: OnceLock<pico_sys_dynamic::ps4000::PS4000Loader> = OnceLock::new();
-
+pub use acquisition::AcquisitionHandle;
+#[cfg(feature = "async")]
+pub use asynchronous::{BlockFuture, BlockStream};
 pub use attenuation::Attenuation;
+pub use block_data::BlockData;
+pub use buffer_pool::BufferPool;
+pub use captured_block::CapturedBlock;
 pub use channel::Channel;
+pub use downsampling::Downsampling;
+pub use driver::{BlockReady, Ps4000Driver, Ps4000Library, StreamingReady, LIBRARY_PATH_ENV};
+pub use error::{Error, Result};
 pub use pico_common::PicoCoupling as Coupling;
-pub use ps4000::PS4262;
+pub use pico_common::PicoStatus;
+pub use ps4000::{Ps4000Device, PS4262};
 pub use range::Range;
-pub use trigger::{ThresholdDirection, Trigger};
+pub use session::BlockSession;
+pub use sig_gen::{
+    ArbitraryWaveform, BuiltInSignal, SigGenTrigSource, SigGenTrigType, SignalGenerator, SweepType,
+    WaveType,
+};
+pub use simulated::{Signal, SimulatedPs4262, Waveform};
+pub use stream::{StreamChunk, StreamConfig, StreamHandle};
+pub use timebase::Timebase;
+pub use trigger::{
+    AdvancedTrigger, PulseWidthQualifier, PulseWidthType, ThresholdDirection, ThresholdMode,
+    Trigger, TriggerCondition, TriggerConfig, TriggerSource, TriggerState,
+};
+pub use unit::{enumerate_units, enumerate_units_with, EnumeratedUnit, PendingOpen, UnitInfo};
+pub use variant::Variant;
 
 #[macro_export]
 macro_rules! check_pico_status {
-    ($x:expr) => {{
-        let status = $x.into();
+    ($x:expr, $function:expr) => {{
+        let status: PicoStatus = $x.into();
         if status != PicoStatus::OK {
-            return Err(status);
+            return Err($crate::Error::Driver {
+                function: $function,
+                status,
+            });
         }
     }};
 }
@@ -4,7 +4,7 @@
  * Created Date: 14/11/2023
  * Author: Shun Suzuki
  * -----
- * Last Modified: 12/12/2023
+ * Last Modified: 18/10/2026
  * Modified By: Shun Suzuki (suzuki@hapis.k.u-tokyo.ac.jp)
  * -----
  * Copyright (c) 2023 Shun Suzuki. All rights reserved.
@@ -27,14 +27,10 @@ fn main() {
             Trigger::new(Channel::A, ThresholdDirection::Rising, 2000.0),
         )
         .unwrap()
-        .join()
-        .unwrap()
+        .wait()
         .unwrap();
 
-    println!(
-        "{:?}",
-        data.get_mv(Channel::A).iter().take(50).collect::<Vec<_>>()
-    );
+    println!("{:?}", data.get_mv(Channel::A).take(50).collect::<Vec<_>>());
     println!("Time interval: {:?}", data.time_interval());
     println!("Overflow: {}", data.overflow());
 }
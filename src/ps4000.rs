@@ -4,7 +4,7 @@
  * Created Date: 14/11/2023
  * Author: Shun Suzuki
  * -----
- * Last Modified: 12/12/2023
+ * Last Modified: 18/10/2026
  * Modified By: Shun Suzuki (suzuki@hapis.k.u-tokyo.ac.jp)
  * -----
  * Copyright (c) 2023 Shun Suzuki. All rights reserved.
@@ -13,289 +13,1044 @@
 
 use std::{
     collections::HashMap,
-    ops::{Index, IndexMut},
-    thread::JoinHandle,
+    ffi::OsStr,
+    ops::{Deref, DerefMut, Index, IndexMut},
+    sync::{
+        mpsc::{self, Receiver},
+        Arc, Mutex, RwLock, Weak,
+    },
+    time::{Duration, Instant},
 };
 
-pub use crate::check_pico_status;
-
-use pico_common::PicoStatus;
+use pico_common::{PicoInfo, PicoStatus};
 use pico_sys_dynamic::ps4000::{
-    enPS4000Channel_PS4000_CHANNEL_A, enPS4000Channel_PS4000_CHANNEL_B,
-    enRatioMode_RATIO_MODE_NONE, enThresholdDirection_NONE, PS4000Loader, PS4262_MAX_VALUE,
+    enPS4000TimeUnits_PS4000_FS, enPS4000TimeUnits_PS4000_MS, enPS4000TimeUnits_PS4000_NS,
+    enPS4000TimeUnits_PS4000_PS, enPS4000TimeUnits_PS4000_US, enPulseWidthType_PW_TYPE_NONE,
+    enThresholdDirection_NONE, PS4000_CHANNEL, PS4000_TIME_UNITS, PS4262_MAX_VALUE,
+    TRIGGER_CHANNEL_PROPERTIES,
 };
 
 use crate::{
-    attenuation::Attenuation,
-    block_data::BlockData,
+    acquisition::{AcquisitionHandle, ReadySignal},
+    block_data::{BlockChannel, BlockData},
+    buffer_pool::{self, BufferPool},
+    captured_block::{BlockReader, CapturedBlock},
     channel::{Channel, ChannelConfig},
-    range::Range,
-    trigger::Trigger,
-    LIBRARY,
+    downsampling::Downsampling,
+    driver::{BlockReady, Ps4000Driver, Ps4000Library},
+    sig_gen::SignalGenerator,
+    stream::{StreamChunk, StreamConfig, StreamHandle},
+    timebase::Timebase,
+    trigger::{AdvancedTrigger, Trigger, TriggerConfig, TriggerSource, TriggerState},
+    unit::{PendingOpen, UnitInfo},
+    variant::{convert_mv_to_adc, Variant},
+    Error, Result,
 };
 
-pub struct PS4262 {
+// Number of timebases tried when the driver rejects the requested one.
+const TIMEBASE_SEARCH_LIMIT: usize = 64;
+
+// Shared with the acquisition threads so that they stop calling the driver once the unit is closed.
+#[derive(Clone, Default)]
+pub(crate) struct HandleGuard {
+    closed: Arc<RwLock<bool>>,
+    // captures waiting for the driver, which does not call back once the unit is closed
+    signals: Arc<Mutex<Vec<Weak<ReadySignal>>>>,
+    // token of the capture last started, so that a handle stops and reads only its own capture
+    run: Arc<Mutex<u64>>,
+}
+
+impl HandleGuard {
+    // The unit cannot be closed while `f` runs.
+    pub(crate) fn with<T>(&self, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        let closed = self.closed.read().unwrap();
+        if *closed {
+            return Err(Error::DeviceClosed);
+        }
+        f()
+    }
+
+    // Starts a capture with `start` and returns its token with the result.
+    pub(crate) fn start<T>(&self, start: impl FnOnce() -> Result<T>) -> Result<(u64, T)> {
+        let mut run = self.run.lock().unwrap();
+        let started = start()?;
+        *run += 1;
+        Ok((*run, started))
+    }
+
+    // Like `with`, but returns `Error::Superseded` unless `run` is the capture last started.
+    pub(crate) fn with_run<T>(&self, run: u64, f: impl FnOnce() -> Result<T>) -> Result<T> {
+        let current = self.run.lock().unwrap();
+        if *current != run {
+            return Err(Error::Superseded);
+        }
+        self.with(f)
+    }
+
+    // Returns a signal for a new capture, which completes with `Error::DeviceClosed` when the unit is closed.
+    pub(crate) fn signal(&self) -> Arc<ReadySignal> {
+        let signal = Arc::new(ReadySignal::default());
+        let mut signals = self.signals.lock().unwrap();
+        signals.retain(|signal| signal.strong_count() > 0);
+        signals.push(Arc::downgrade(&signal));
+        if *self.closed.read().unwrap() {
+            signal.close();
+        }
+        signal
+    }
+
+    fn close(&self) {
+        *self.closed.write().unwrap() = true;
+        let signals = std::mem::take(&mut *self.signals.lock().unwrap());
+        signals
+            .iter()
+            .filter_map(Weak::upgrade)
+            .for_each(|signal| signal.close());
+    }
+}
+
+// Restores a single memory segment and capture when dropped, so that a rapid block capture that completes,
+// fails or is cancelled leaves the whole memory to later block captures.
+struct SegmentedMemory<D: Ps4000Driver> {
+    driver: Arc<D>,
     handle: i16,
-    channels: [ChannelConfig; 2],
+    guard: HandleGuard,
 }
 
-impl PS4262 {
-    pub const MAX_VALUE: u32 = PS4262_MAX_VALUE;
-    pub const MAX_CHANNELS: usize = 2;
+impl<D: Ps4000Driver> Drop for SegmentedMemory<D> {
+    fn drop(&mut self) {
+        let _ = self.guard.with(|| {
+            // the segments cannot be changed while a capture is running
+            let _ = self.driver.stop(self.handle);
+            self.driver.set_no_of_captures(self.handle, 1)?;
+            self.driver.memory_segments(self.handle, 1)
+        });
+    }
+}
+
+/// A PicoScope 4000 series unit of any supported [`Variant`].
+///
+/// The unit is closed when dropped.
+pub struct Ps4000Device<D: Ps4000Driver + 'static = Ps4000Library> {
+    driver: Arc<D>,
+    handle: i16,
+    guard: HandleGuard,
+    variant: Variant,
+    channels: Vec<ChannelConfig>,
+    downsampling: Downsampling,
+    buffer_pool: Option<BufferPool>,
+}
+
+impl Ps4000Device {
+    pub fn new() -> Result<Self> {
+        Self::with_driver(Ps4000Library::new()?)
+    }
+
+    pub fn with_library_path(path: impl AsRef<OsStr>) -> Result<Self> {
+        Self::with_driver(Ps4000Library::with_path(path)?)
+    }
 
-    pub fn new() -> Result<Self, PicoStatus> {
-        let handle = unsafe {
-            let library = LIBRARY.get_or_init(|| PS4000Loader::new("ps4000.dll").unwrap());
-            let mut handle = -1;
-            check_pico_status!(library.ps4000OpenUnit(&mut handle as _));
-            handle
+    /// Opens the unit with the serial number `serial`, as listed by [`crate::enumerate_units`].
+    pub fn open_by_serial(serial: &str) -> Result<Self> {
+        Self::with_driver_and_serial(Ps4000Library::new()?, serial)
+    }
+
+    /// Starts opening the first unit found and returns immediately, see [`PendingOpen`].
+    pub fn open_async() -> Result<PendingOpen> {
+        Self::open_async_with_driver(Ps4000Library::new()?)
+    }
+}
+
+impl<D: Ps4000Driver + 'static> Ps4000Device<D> {
+    pub fn with_driver(driver: D) -> Result<Self> {
+        let handle = driver.open_unit()?;
+        Self::from_handle(driver, handle)
+    }
+
+    pub fn with_driver_and_serial(driver: D, serial: &str) -> Result<Self> {
+        let handle = driver.open_unit_ex(serial)?;
+        Self::from_handle(driver, handle)
+    }
+
+    pub fn open_async_with_driver(driver: D) -> Result<PendingOpen<D>> {
+        driver.open_unit_async()?;
+        Ok(PendingOpen::new(driver))
+    }
+
+    pub(crate) fn from_handle(driver: D, handle: i16) -> Result<Self> {
+        let variant = match driver
+            .get_unit_info(handle, PicoInfo::VARIANT_INFO)
+            .and_then(|variant| variant.parse::<Variant>())
+        {
+            Ok(variant) => variant,
+            Err(e) => {
+                let _ = driver.close_unit(handle);
+                return Err(e);
+            }
         };
 
         let pico = Self {
+            driver: Arc::new(driver),
             handle,
-            channels: [
-                ChannelConfig::new(enPS4000Channel_PS4000_CHANNEL_A, handle),
-                ChannelConfig::new(enPS4000Channel_PS4000_CHANNEL_B, handle),
-            ],
+            guard: HandleGuard::default(),
+            variant,
+            channels: (0..variant.channel_count())
+                .map(|ch| ChannelConfig::new(ch as PS4000_CHANNEL, handle))
+                .collect(),
+            downsampling: Downsampling::None,
+            buffer_pool: None,
         };
 
-        pico.channels.iter().for_each(|ch| ch.update().unwrap());
+        pico.update_channels()?;
 
         Ok(pico)
     }
 
-    pub fn close(&mut self) -> Result<(), PicoStatus> {
-        unsafe {
-            if self.handle < 0 {
-                return Ok(());
-            }
+    pub fn driver(&self) -> &D {
+        &self.driver
+    }
+
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
+
+    /// Reads the versions, serial number and calibration date of the unit.
+    pub fn info(&self) -> Result<UnitInfo> {
+        UnitInfo::query(self.driver.as_ref(), self.handle)
+    }
+
+    pub fn downsampling(&self) -> Downsampling {
+        self.downsampling
+    }
 
-            let library = LIBRARY.get().unwrap();
-            check_pico_status!(library.ps4000CloseUnit(self.handle));
-            self.handle = -1;
+    /// Sets the downsampling applied when retrieving block captures; rapid block captures are not downsampled.
+    ///
+    /// The ratio is checked against the maximum the driver allows once a capture completes.
+    pub fn set_downsampling(&mut self, downsampling: Downsampling) -> Result<()> {
+        if downsampling.ratio() == 0 {
+            return Err(Error::InvalidConfig(
+                "downsampling ratio must be at least 1".to_string(),
+            ));
         }
+        self.downsampling = downsampling;
         Ok(())
     }
 
-    fn disable_trigger(&self) -> Result<(), PicoStatus> {
-        unsafe {
-            let library = LIBRARY.get().unwrap();
-            check_pico_status!(library.ps4000SetSimpleTrigger(
-                self.handle,
-                0,
-                Channel::A.into(),
-                0,
-                enThresholdDirection_NONE,
-                0,
-                0
+    /// Makes block captures take their buffers from `pool` and return them when the [`BlockData`] is dropped.
+    pub fn set_buffer_pool(&mut self, pool: BufferPool) {
+        self.buffer_pool = Some(pool);
+    }
+
+    /// Returns the signal generator, or an error if this variant does not have one.
+    pub fn signal_generator(&self) -> Result<SignalGenerator<'_, D>> {
+        match (
+            self.variant.sig_gen_max_frequency(),
+            self.variant.sig_gen_max_voltage_uv(),
+        ) {
+            (Some(max_frequency), Some(max_voltage_uv)) => Ok(SignalGenerator {
+                driver: self.driver.as_ref(),
+                handle: self.handle,
+                max_frequency,
+                max_voltage_uv,
+            }),
+            _ => Err(Error::InvalidConfig(format!(
+                "signal generator is not available on {}",
+                self.variant
+            ))),
+        }
+    }
+
+    /// Stops any running capture and closes the unit; acquisitions still in progress fail with [`Error::DeviceClosed`].
+    pub fn close(&mut self) -> Result<()> {
+        if self.handle < 0 {
+            return Ok(());
+        }
+
+        self.guard.close();
+        let _ = self.driver.stop(self.handle);
+        self.driver.close_unit(self.handle)?;
+        self.handle = -1;
+        Ok(())
+    }
+
+    /// Returns the settings of `channel`, or an error if this variant does not have it.
+    pub fn channel(&self, channel: Channel) -> Result<&ChannelConfig> {
+        self.check_channel(channel)?;
+        Ok(&self[channel])
+    }
+
+    pub fn channel_mut(&mut self, channel: Channel) -> Result<&mut ChannelConfig> {
+        self.check_channel(channel)?;
+        Ok(&mut self[channel])
+    }
+
+    fn check_channel(&self, channel: Channel) -> Result<()> {
+        self.check_source(channel.into())
+    }
+
+    fn check_source(&self, source: TriggerSource) -> Result<()> {
+        if !self.variant.has_trigger_source(source) {
+            return Err(Error::InvalidConfig(format!(
+                "input {:?} is not available on {}",
+                source, self.variant
+            )));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn update_channels(&self) -> Result<()> {
+        self.channels.iter().try_for_each(|ch| {
+            self.variant.validate_range(ch.range, ch.attenuation)?;
+            ch.update(self.driver.as_ref())
+        })
+    }
+
+    /// Resolves the timebase whose interval is nearest to `1 / sample_rate` seconds for the current channel setup.
+    ///
+    /// Rates below that of the slowest timebase resolve to the slowest one.
+    pub fn timebase_for_rate(&self, sample_rate: f64) -> Result<Timebase> {
+        if !(sample_rate.is_finite() && sample_rate > 0.) {
+            return Err(Error::InvalidConfig(format!(
+                "sample rate {} must be positive",
+                sample_rate
+            )));
+        }
+        self.update_channels()?;
+        self.resolve_timebase(self.variant.nearest_timebase(1. / sample_rate), 0)
+    }
+
+    /// Resolves the timebase whose interval is nearest to `interval` for the current channel setup.
+    pub fn timebase_for_interval(&self, interval: Duration) -> Result<Timebase> {
+        if interval.is_zero() {
+            return Err(Error::InvalidConfig(
+                "sampling interval must be positive".to_string(),
             ));
-            check_pico_status!(library.ps4000SetSimpleTrigger(
+        }
+        self.update_channels()?;
+        self.resolve_timebase(self.variant.nearest_timebase(interval.as_secs_f64()), 0)
+    }
+
+    // Resolves the timebase for a capture of `no_of_samples` samples at `sample_rate`.
+    pub(crate) fn capture_timebase(
+        &self,
+        sample_rate: u32,
+        no_of_samples: u32,
+    ) -> Result<Timebase> {
+        if sample_rate == 0 {
+            return Err(Error::InvalidConfig(
+                "sample rate must be positive".to_string(),
+            ));
+        }
+        self.resolve_timebase(
+            self.variant.nearest_timebase(1. / sample_rate as f64),
+            no_of_samples as i32,
+        )
+    }
+
+    // The driver may reject a timebase for the current channel setup, in which case a slower one is tried.
+    fn resolve_timebase(&self, timebase: u32, no_of_samples: i32) -> Result<Timebase> {
+        let mut result = Err(Error::InvalidConfig(format!(
+            "no timebase from {} is accepted by the driver",
+            timebase
+        )));
+        for timebase in (timebase..=u32::MAX).take(TIMEBASE_SEARCH_LIMIT) {
+            match self
+                .driver
+                .get_timebase(self.handle, timebase, no_of_samples, 1, 0)
+            {
+                Ok((interval_ns, max_samples)) => {
+                    return Ok(Timebase::new(timebase, interval_ns, max_samples))
+                }
+                Err(e) if e.status() == Some(PicoStatus::INVALID_TIMEBASE) => result = Err(e),
+                Err(e) => return Err(e),
+            }
+        }
+        result
+    }
+
+    fn mv_to_adc(&self, channel: Channel, mv: f64) -> Result<i16> {
+        let ch = &self[channel];
+        Ok(convert_mv_to_adc(
+            mv,
+            self.variant.max_value(),
+            ch.attenuation,
+            ch.range.mv(ch.attenuation)?,
+        ))
+    }
+
+    fn disable_trigger(&self) -> Result<()> {
+        self.channels.iter().try_for_each(|ch| {
+            self.driver.set_simple_trigger(
                 self.handle,
-                0,
-                Channel::B.into(),
+                false,
+                ch.channel,
                 0,
                 enThresholdDirection_NONE,
                 0,
-                0
-            ));
-        }
-        Ok(())
+                0,
+            )
+        })
+    }
+
+    fn set_trigger(&self, cond: Trigger) -> Result<()> {
+        self.check_channel(cond.channel)?;
+        self.driver.set_simple_trigger(
+            self.handle,
+            true,
+            cond.channel.into(),
+            self.mv_to_adc(cond.channel, cond.value_mv)?,
+            cond.dir.into(),
+            cond.delay,
+            cond.auto_trigger_ms,
+        )
     }
 
-    fn set_trigger(&self, cond: Trigger) -> Result<(), PicoStatus> {
-        unsafe {
-            let library = LIBRARY.get().unwrap();
+    fn set_advanced_trigger(&self, cond: &AdvancedTrigger, time_interval_ns: i32) -> Result<()> {
+        let conditions = cond.conditions()?;
+        let pulse_width_conditions = cond.pulse_width_conditions()?;
+        cond.thresholds
+            .iter()
+            .try_for_each(|t| self.check_channel(t.channel))?;
+        [
+            (TriggerSource::External, cond.ext_dir),
+            (TriggerSource::Aux, cond.aux_dir),
+        ]
+        .into_iter()
+        .filter(|(_, dir)| dir.is_some())
+        .try_for_each(|(source, _)| self.check_source(source))?;
+        conditions
+            .iter()
+            .chain(pulse_width_conditions.iter())
+            .flat_map(|c| {
+                TriggerSource::ALL
+                    .into_iter()
+                    .filter(|&source| c.state(source) != TriggerState::DoNotCare)
+            })
+            .try_for_each(|source| self.check_source(source))?;
 
-            check_pico_status!(library.ps4000SetSimpleTrigger(
+        let conditions = conditions.into_iter().map(Into::into).collect::<Vec<_>>();
+        let properties = cond
+            .thresholds
+            .iter()
+            .map(|t| {
+                t.validate()?;
+                let to_adc = |mv| self.mv_to_adc(t.channel, mv);
+                let hysteresis = to_adc(t.hysteresis_mv)?.unsigned_abs();
+                Ok(TRIGGER_CHANNEL_PROPERTIES {
+                    thresholdUpper: to_adc(t.upper_mv)?,
+                    thresholdUpperHysteresis: hysteresis,
+                    thresholdLower: to_adc(t.lower_mv)?,
+                    thresholdLowerHysteresis: hysteresis,
+                    channel: t.channel.into(),
+                    thresholdMode: t.mode.into(),
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        self.driver
+            .set_trigger_channel_conditions(self.handle, &conditions)?;
+        self.driver.set_trigger_channel_directions(
+            self.handle,
+            cond.direction(TriggerSource::A).into(),
+            cond.direction(TriggerSource::B).into(),
+            cond.direction(TriggerSource::C).into(),
+            cond.direction(TriggerSource::D).into(),
+            cond.direction(TriggerSource::External).into(),
+            cond.direction(TriggerSource::Aux).into(),
+        )?;
+        self.driver.set_trigger_channel_properties(
+            self.handle,
+            &properties,
+            false,
+            cond.auto_trigger_ms,
+        )?;
+        self.driver.set_trigger_delay(self.handle, cond.delay)?;
+
+        match &cond.pulse_width_qualifier {
+            Some(pwq) => {
+                let (lower, upper) = pwq.widths(time_interval_ns)?;
+                let conditions = pulse_width_conditions
+                    .into_iter()
+                    .map(Into::into)
+                    .collect::<Vec<_>>();
+                self.driver.set_pulse_width_qualifier(
+                    self.handle,
+                    &conditions,
+                    pwq.dir.into(),
+                    lower,
+                    upper,
+                    pwq.ty.into(),
+                )
+            }
+            None => self.driver.set_pulse_width_qualifier(
                 self.handle,
-                1,
-                cond.channel.into(),
-                Self::convert_mv_to_adc(
-                    cond.value_mv,
-                    self[cond.channel].attenuation,
-                    self[cond.channel].range
-                ),
-                cond.dir.into(),
-                cond.delay,
-                cond.auto_trigger_ms,
-            ));
+                &[],
+                enThresholdDirection_NONE,
+                0,
+                0,
+                enPulseWidthType_PW_TYPE_NONE,
+            ),
         }
+    }
 
-        Ok(())
+    // Pulse widths are converted with `time_interval_ns`, so the trigger is set once the timebase is known.
+    pub(crate) fn apply_trigger(
+        &self,
+        cond: Option<&TriggerConfig>,
+        time_interval_ns: i32,
+    ) -> Result<()> {
+        match cond {
+            Some(TriggerConfig::Simple(cond)) => self.set_trigger(*cond),
+            Some(TriggerConfig::Advanced(cond)) => {
+                self.set_advanced_trigger(cond, time_interval_ns)
+            }
+            None => self.disable_trigger(),
+        }
     }
 
     fn block_data_handler(
         &self,
         buffer_size: u32,
-        timebase: u32,
-        no_of_pre_trigger_samples: i32,
-    ) -> Result<JoinHandle<Result<BlockData, PicoStatus>>, PicoStatus> {
-        let mut timebase = timebase;
-        let mut buffer_size = buffer_size;
-        let sample_count = buffer_size as i32;
-        let mut min_pinned = HashMap::new();
-        min_pinned.insert(self[Channel::A].channel, vec![0i16; buffer_size as _]);
-        min_pinned.insert(self[Channel::B].channel, vec![0i16; buffer_size as _]);
-        let mut max_pinned = HashMap::new();
-        max_pinned.insert(self[Channel::A].channel, vec![0i16; buffer_size as _]);
-        max_pinned.insert(self[Channel::B].channel, vec![0i16; buffer_size as _]);
-
-        unsafe {
-            let library = LIBRARY.get().unwrap();
-            check_pico_status!(library.ps4000SetDataBuffers(
-                self.handle,
-                self[Channel::A].channel,
-                max_pinned
-                    .get_mut(&self[Channel::A].channel)
-                    .unwrap()
-                    .as_mut_ptr(),
-                min_pinned
-                    .get_mut(&self[Channel::A].channel)
-                    .unwrap()
-                    .as_mut_ptr(),
-                sample_count,
-            ));
-            check_pico_status!(library.ps4000SetDataBuffers(
-                self.handle,
-                self[Channel::B].channel,
-                max_pinned
-                    .get_mut(&self[Channel::B].channel)
-                    .unwrap()
-                    .as_mut_ptr(),
-                min_pinned
-                    .get_mut(&self[Channel::B].channel)
-                    .unwrap()
-                    .as_mut_ptr(),
-                sample_count,
-            ));
+        timebase: Timebase,
+        cond: Option<&TriggerConfig>,
+    ) -> Result<AcquisitionHandle<BlockData>> {
+        let signal = self.guard.signal();
+        let (retrieve, stop) = self.start_block(buffer_size, timebase, cond, signal.callback())?;
+        Ok(AcquisitionHandle::spawn(signal, stop, retrieve))
+    }
 
-            let mut time_interval_nanoseconds = 0i32;
-            let mut max_samples = 0;
+    // Starts a block capture and returns the closures that retrieve its data once `ready` is called and that stop it.
+    pub(crate) fn start_block(
+        &self,
+        buffer_size: u32,
+        timebase: Timebase,
+        cond: Option<&TriggerConfig>,
+        ready: BlockReady,
+    ) -> Result<(
+        impl FnOnce() -> Result<BlockData> + Send + 'static,
+        impl Fn() -> Result<()> + Send + 'static,
+    )> {
+        let reader = self.block_reader(buffer_size, timebase)?;
+        self.apply_trigger(cond, timebase.interval_ns)?;
+        let run = self.run_block(buffer_size, timebase, cond, ready)?;
 
-            while library.ps4000GetTimebase(
-                self.handle,
-                timebase,
-                sample_count,
-                &mut time_interval_nanoseconds as _,
-                1,
-                &mut max_samples as _,
-                0,
-            ) != 0
-            {
-                timebase += 1;
-            }
+        let downsampling = self.downsampling;
+        let guard = self.guard.clone();
+        let retrieve = move || {
+            guard.with_run(run, || {
+                reader.driver.stop(reader.handle)?;
+                reader.read(0, buffer_size, downsampling)
+            })
+        };
+        Ok((retrieve, self.stopper(run)))
+    }
 
-            let mut time_in_disposed_ms = 0;
-            check_pico_status!(library.ps4000RunBlock(
+    // Runs a capture with the trigger already applied and returns its token.
+    fn run_block(
+        &self,
+        sample_count: u32,
+        timebase: Timebase,
+        cond: Option<&TriggerConfig>,
+        ready: BlockReady,
+    ) -> Result<u64> {
+        let sample_count = sample_count as i32;
+        let no_of_pre_trigger_samples = cond.map_or(0, |cond| cond.no_of_pre_trigger_samples());
+        let (run, _) = self.guard.start(|| {
+            self.driver.run_block_with_callback(
                 self.handle,
                 no_of_pre_trigger_samples,
                 sample_count - no_of_pre_trigger_samples,
-                timebase,
+                timebase.timebase,
                 1,
-                &mut time_in_disposed_ms as _,
                 0,
-                None,
-                std::ptr::null_mut(),
-            ));
+                ready,
+            )
+        })?;
+        Ok(run)
+    }
+
+    // Runs a capture with the trigger already applied and blocks until it completes,
+    // or stops it and returns `Error::Timeout` after `timeout`.
+    pub(crate) fn run_block_and_wait(
+        &self,
+        sample_count: u32,
+        timebase: Timebase,
+        cond: Option<&TriggerConfig>,
+        timeout: Option<Duration>,
+    ) -> Result<()> {
+        let signal = self.guard.signal();
+        self.run_block(sample_count, timebase, cond, signal.callback())?;
 
-            let handle = self.handle;
-            let ranges = self
+        let ready = signal
+            .wait(timeout.map(|timeout| Instant::now() + timeout))
+            // only an `AcquisitionHandle` cancels its signal
+            .unwrap_or(Err(Error::Superseded));
+        if let Err(e) = ready {
+            let _ = self.driver.stop(self.handle);
+            return Err(e);
+        }
+        self.driver.stop(self.handle)
+    }
+
+    pub(crate) fn block_reader(
+        &self,
+        sample_count: u32,
+        timebase: Timebase,
+    ) -> Result<BlockReader<D>> {
+        Ok(BlockReader {
+            driver: self.driver.clone(),
+            handle: self.handle,
+            sample_count,
+            time_interval_ns: timebase.interval_ns,
+            enabled: self
                 .channels
                 .iter()
-                .map(|ch| (ch.channel, ch.range))
-                .collect();
-            let attenuations = self
+                .filter(|ch| ch.enable)
+                .map(|ch| {
+                    Ok(BlockChannel {
+                        channel: ch.channel,
+                        range_mv: ch.range.mv(ch.attenuation)?,
+                        attenuation: ch.attenuation,
+                    })
+                })
+                .collect::<Result<_>>()?,
+            disabled: self
                 .channels
                 .iter()
-                .map(|ch| (ch.channel, ch.attenuation))
-                .collect();
-            Ok(std::thread::spawn(move || {
-                loop {
-                    let mut ready = 0i16;
-                    check_pico_status!(library.ps4000IsReady(handle, &mut ready as _));
-                    if ready != 0 {
-                        break;
-                    }
-                }
+                .filter(|ch| !ch.enable)
+                .map(|ch| ch.channel)
+                .collect(),
+            max_value: self.variant.max_value(),
+            pool: self.buffer_pool.clone(),
+        })
+    }
 
-                check_pico_status!(library.ps4000Stop(handle));
-
-                let mut overflow = 0;
-                check_pico_status!(library.ps4000GetValues(
-                    handle,
-                    0,
-                    &mut buffer_size as _,
-                    1,
-                    enRatioMode_RATIO_MODE_NONE as _,
-                    0,
-                    &mut overflow,
-                ));
-
-                Ok(BlockData::new(
-                    buffer_size,
-                    overflow,
-                    time_interval_nanoseconds,
-                    min_pinned,
-                    max_pinned,
-                    ranges,
-                    attenuations,
-                ))
-            }))
+    // Stops the capture `run`, unless another capture has been started or the unit closed meanwhile.
+    fn stopper(&self, run: u64) -> impl Fn() -> Result<()> + Send + 'static {
+        let driver = self.driver.clone();
+        let handle = self.handle;
+        let guard = self.guard.clone();
+        move || match guard.with_run(run, || driver.stop(handle)) {
+            Err(Error::Superseded) => Ok(()),
+            stopped => stopped,
         }
     }
 
+    #[cfg(feature = "async")]
+    // A signal for a new capture, completed with `Error::DeviceClosed` if the unit is closed first.
+    pub(crate) fn ready_signal(&self) -> Arc<ReadySignal> {
+        self.guard.signal()
+    }
+
     pub fn collect_block_immediate(
         &self,
         sample_count: u32,
         sample_rate: u32,
-    ) -> Result<BlockData, PicoStatus> {
-        self.channels.iter().try_for_each(|ch| ch.update())?;
-        self.disable_trigger()?;
-        let timebase = 10000000 / sample_rate - 1;
-        self.block_data_handler(sample_count, timebase, 0)?
-            .join()
-            .unwrap()
+    ) -> Result<BlockData> {
+        self.update_channels()?;
+        let timebase = self.capture_timebase(sample_rate, sample_count)?;
+        self.block_data_handler(sample_count, timebase, None)?
+            .wait()
     }
 
     pub fn collect_block_triggered(
         &self,
         sample_count: u32,
         sample_rate: u32,
-        cond: Trigger,
-    ) -> Result<JoinHandle<Result<BlockData, PicoStatus>>, PicoStatus> {
-        self.channels.iter().try_for_each(|ch| ch.update())?;
-        self.set_trigger(cond)?;
-        let timebase = 10000000 / sample_rate - 1;
-        Ok(self.block_data_handler(sample_count, timebase, cond.no_of_pre_trigger_samples)?)
+        cond: impl Into<TriggerConfig>,
+    ) -> Result<AcquisitionHandle<BlockData>> {
+        let cond = cond.into();
+        self.update_channels()?;
+        let timebase = self.capture_timebase(sample_rate, sample_count)?;
+        self.block_data_handler(sample_count, timebase, Some(&cond))
+    }
+
+    /// Captures a block immediately and keeps it in the device's memory to be read in parts.
+    pub fn capture_block(
+        &mut self,
+        sample_count: u32,
+        sample_rate: u32,
+    ) -> Result<CapturedBlock<'_, D>> {
+        self.capture(sample_count, sample_rate, None, None)
+    }
+
+    /// Captures a triggered block and keeps it in the device's memory to be read in parts.
+    ///
+    /// Blocks until the trigger fires, or stops the capture and returns [`Error::Timeout`] after `timeout`.
+    pub fn capture_block_triggered(
+        &mut self,
+        sample_count: u32,
+        sample_rate: u32,
+        cond: impl Into<TriggerConfig>,
+        timeout: Duration,
+    ) -> Result<CapturedBlock<'_, D>> {
+        self.capture(sample_count, sample_rate, Some(&cond.into()), Some(timeout))
+    }
+
+    fn capture(
+        &mut self,
+        sample_count: u32,
+        sample_rate: u32,
+        cond: Option<&TriggerConfig>,
+        timeout: Option<Duration>,
+    ) -> Result<CapturedBlock<'_, D>> {
+        self.update_channels()?;
+        let timebase = self.capture_timebase(sample_rate, sample_count)?;
+        let reader = self.block_reader(sample_count, timebase)?;
+        self.apply_trigger(cond, timebase.interval_ns)?;
+        self.run_block_and_wait(sample_count, timebase, cond, timeout)?;
+        Ok(CapturedBlock {
+            reader,
+            _device: self,
+        })
+    }
+
+    /// Captures `segments` triggered blocks back-to-back into segmented memory.
+    pub fn collect_rapid_block(
+        &self,
+        segments: u16,
+        samples_per_segment: u32,
+        sample_rate: u32,
+        cond: impl Into<TriggerConfig>,
+    ) -> Result<AcquisitionHandle<Vec<BlockData>>> {
+        let cond = cond.into();
+        if segments == 0 {
+            return Err(Error::InvalidConfig(
+                "number of segments must be at least 1".to_string(),
+            ));
+        }
+
+        self.update_channels()?;
+
+        let max_samples = self.driver.memory_segments(self.handle, segments)?;
+        let segmented = SegmentedMemory {
+            driver: self.driver.clone(),
+            handle: self.handle,
+            guard: self.guard.clone(),
+        };
+        if samples_per_segment as i64 > max_samples as i64 {
+            return Err(Error::InvalidConfig(format!(
+                "{} samples per segment exceeds the maximum of {} for {} segments",
+                samples_per_segment, max_samples, segments
+            )));
+        }
+        self.driver.set_no_of_captures(self.handle, segments)?;
+        let timebase = self.capture_timebase(sample_rate, samples_per_segment)?;
+
+        let sample_count = samples_per_segment as i32;
+        let reader = self.block_reader(samples_per_segment, timebase)?;
+        let stride = samples_per_segment as usize;
+        let mut buffers: Vec<_> = (0..segments)
+            .map(|_| buffer_pool::alloc(reader.pool.as_ref(), stride * reader.enabled.len()))
+            .collect();
+        for (segment, buffer) in buffers.iter_mut().enumerate() {
+            for &channel in reader.disabled.iter() {
+                unsafe {
+                    self.driver.set_data_buffer_bulk(
+                        self.handle,
+                        channel,
+                        std::ptr::null_mut(),
+                        0,
+                        segment as u16,
+                    )?;
+                }
+            }
+            for (i, ch) in reader.enabled.iter().enumerate() {
+                unsafe {
+                    self.driver.set_data_buffer_bulk(
+                        self.handle,
+                        ch.channel,
+                        buffer.as_mut_ptr().add(i * stride),
+                        sample_count,
+                        segment as u16,
+                    )?;
+                }
+            }
+        }
+
+        let time_interval_nanoseconds = timebase.interval_ns;
+        self.apply_trigger(Some(&cond), time_interval_nanoseconds)?;
+        let no_of_pre_trigger_samples = cond.no_of_pre_trigger_samples();
+        let signal = self.guard.signal();
+        let (run, _) = self.guard.start(|| {
+            self.driver.run_block_with_callback(
+                self.handle,
+                no_of_pre_trigger_samples,
+                sample_count - no_of_pre_trigger_samples,
+                timebase.timebase,
+                1,
+                0,
+                signal.callback(),
+            )
+        })?;
+
+        let guard = self.guard.clone();
+        Ok(AcquisitionHandle::spawn(
+            signal,
+            self.stopper(run),
+            move || {
+                let _segmented = segmented;
+                let driver = &reader.driver;
+                let handle = reader.handle;
+                let last = segments - 1;
+                let mut overflow = vec![0i16; segments as _];
+                let mut times = vec![0i64; segments as _];
+                let mut time_units = vec![enPS4000TimeUnits_PS4000_NS; segments as _];
+                let sample_count = guard.with_run(run, || {
+                    driver.stop(handle)?;
+                    let sample_count = driver.get_values_bulk(
+                        handle,
+                        samples_per_segment,
+                        0,
+                        last,
+                        &mut overflow,
+                    )?;
+                    driver.get_values_trigger_time_offset_bulk64(
+                        handle,
+                        &mut times,
+                        &mut time_units,
+                        0,
+                        last,
+                    )?;
+                    Ok(sample_count)
+                })?;
+
+                Ok(buffers
+                    .into_iter()
+                    .zip(overflow)
+                    .zip(times.into_iter().zip(time_units))
+                    .map(|((buffer, overflow), (time, unit))| {
+                        BlockData::new(
+                            sample_count,
+                            stride,
+                            overflow,
+                            time_interval_nanoseconds,
+                            reader.enabled.clone(),
+                            buffer,
+                            Vec::new(),
+                            reader.max_value,
+                        )
+                        .with_pool(reader.pool.clone())
+                        .with_trigger_time_offset(time as f64 * time_unit_seconds(unit))
+                    })
+                    .collect())
+            },
+        ))
+    }
+
+    fn streaming_handler(
+        &self,
+        config: StreamConfig,
+        rx: Option<Receiver<StreamChunk>>,
+        sink: Box<dyn FnMut(StreamChunk) + Send>,
+    ) -> Result<StreamHandle> {
+        if config.sample_rate == 0 || config.sample_rate > 1_000_000_000 {
+            return Err(Error::InvalidConfig(format!(
+                "sample rate {} is out of range",
+                config.sample_rate
+            )));
+        }
+
+        self.update_channels()?;
+        match config.trigger {
+            Some(cond) => self.set_trigger(cond)?,
+            None => self.disable_trigger()?,
+        }
+
+        let mut buffers: HashMap<_, _> = self
+            .channels
+            .iter()
+            .filter(|ch| ch.enable)
+            .map(|ch| (ch.channel, vec![0i16; config.buffer_size as _]))
+            .collect();
+        for (&channel, buffer) in buffers.iter_mut() {
+            unsafe {
+                self.driver.set_data_buffers(
+                    self.handle,
+                    channel,
+                    buffer.as_mut_ptr(),
+                    std::ptr::null_mut(),
+                    config.buffer_size as _,
+                )?;
+            }
+        }
+
+        let no_of_pre_trigger_samples = config
+            .trigger
+            .map_or(0, |cond| cond.no_of_pre_trigger_samples.max(0) as u32);
+        let (_, sample_interval_ns) = self.guard.start(|| {
+            self.driver.run_streaming(
+                self.handle,
+                1_000_000_000 / config.sample_rate,
+                enPS4000TimeUnits_PS4000_NS,
+                no_of_pre_trigger_samples,
+                config.auto_stop.unwrap_or(config.buffer_size),
+                config.auto_stop.is_some(),
+                1,
+                config.buffer_size,
+            )
+        })?;
+
+        let ranges = self
+            .channels
+            .iter()
+            .map(|ch| Ok((ch.channel, ch.range.mv(ch.attenuation)?)))
+            .collect::<Result<_>>()?;
+        let attenuations = self
+            .channels
+            .iter()
+            .map(|ch| (ch.channel, ch.attenuation))
+            .collect();
+        Ok(StreamHandle::spawn(
+            self.driver.clone(),
+            self.handle,
+            buffers,
+            ranges,
+            attenuations,
+            self.variant.max_value(),
+            self.guard.clone(),
+            Duration::from_nanos(sample_interval_ns as _),
+            rx,
+            sink,
+        ))
+    }
+
+    /// Starts streaming; chunks are received by iterating over the returned handle.
+    pub fn start_streaming(&self, config: StreamConfig) -> Result<StreamHandle> {
+        let (tx, rx) = mpsc::channel();
+        self.streaming_handler(
+            config,
+            Some(rx),
+            Box::new(move |chunk| {
+                let _ = tx.send(chunk);
+            }),
+        )
     }
 
-    pub(crate) fn convert_adc_to_mv(raw: i16, attenuation: Attenuation, range: Range) -> f64 {
-        (raw as i32 * range.mv(attenuation).unwrap()) as f64 / Self::MAX_VALUE as f64
-            * attenuation.value()
+    /// Starts streaming; `callback` is invoked on the worker thread for each chunk.
+    pub fn start_streaming_with_callback(
+        &self,
+        config: StreamConfig,
+        callback: impl FnMut(StreamChunk) + Send + 'static,
+    ) -> Result<StreamHandle> {
+        self.streaming_handler(config, None, Box::new(callback))
     }
+}
 
-    pub(crate) fn convert_mv_to_adc(raw: f64, attenuation: Attenuation, range: Range) -> i16 {
-        (raw / attenuation.value() * Self::MAX_VALUE as f64 / range.mv(attenuation).unwrap() as f64)
-            as i16
+#[allow(non_upper_case_globals)]
+fn time_unit_seconds(unit: PS4000_TIME_UNITS) -> f64 {
+    match unit {
+        enPS4000TimeUnits_PS4000_FS => 1e-15,
+        enPS4000TimeUnits_PS4000_PS => 1e-12,
+        enPS4000TimeUnits_PS4000_NS => 1e-9,
+        enPS4000TimeUnits_PS4000_US => 1e-6,
+        enPS4000TimeUnits_PS4000_MS => 1e-3,
+        _ => 1.,
     }
 }
 
-impl Index<Channel> for PS4262 {
+impl<D: Ps4000Driver + 'static> Drop for Ps4000Device<D> {
+    fn drop(&mut self) {
+        let _ = self.close();
+    }
+}
+
+/// # Panics
+///
+/// Panics if this variant does not have the channel, such as C or D on a 2-channel model;
+/// [`Ps4000Device::channel`] returns an error instead.
+impl<D: Ps4000Driver + 'static> Index<Channel> for Ps4000Device<D> {
     type Output = ChannelConfig;
 
     fn index(&self, ch: Channel) -> &Self::Output {
-        match ch {
-            Channel::A => &self.channels[0],
-            Channel::B => &self.channels[1],
-        }
+        self.channels
+            .get(PS4000_CHANNEL::from(ch) as usize)
+            .unwrap_or_else(|| panic!("channel {:?} is not available on {}", ch, self.variant))
     }
 }
 
-impl IndexMut<Channel> for PS4262 {
+/// # Panics
+///
+/// Panics if this variant does not have the channel, such as C or D on a 2-channel model;
+/// [`Ps4000Device::channel_mut`] returns an error instead.
+impl<D: Ps4000Driver + 'static> IndexMut<Channel> for Ps4000Device<D> {
     fn index_mut(&mut self, ch: Channel) -> &mut Self::Output {
-        match ch {
-            Channel::A => &mut self.channels[0],
-            Channel::B => &mut self.channels[1],
+        let variant = self.variant;
+        self.channels
+            .get_mut(PS4000_CHANNEL::from(ch) as usize)
+            .unwrap_or_else(|| panic!("channel {:?} is not available on {}", ch, variant))
+    }
+}
+
+/// A [`Ps4000Device`] that is known to be a PicoScope 4262.
+pub struct PS4262<D: Ps4000Driver + 'static = Ps4000Library>(Ps4000Device<D>);
+
+impl PS4262 {
+    pub const MAX_VALUE: u32 = PS4262_MAX_VALUE;
+    pub const MAX_CHANNELS: usize = 2;
+
+    pub fn new() -> Result<Self> {
+        Self::with_driver(Ps4000Library::new()?)
+    }
+
+    pub fn with_library_path(path: impl AsRef<OsStr>) -> Result<Self> {
+        Self::with_driver(Ps4000Library::with_path(path)?)
+    }
+
+    /// Opens the unit with the serial number `serial`, as listed by [`crate::enumerate_units`].
+    pub fn open_by_serial(serial: &str) -> Result<Self> {
+        Self::with_driver_and_serial(Ps4000Library::new()?, serial)
+    }
+
+    /// Starts opening the first unit found and returns immediately, see [`PendingOpen`].
+    pub fn open_async() -> Result<PendingOpen<Ps4000Library, Self>> {
+        Self::open_async_with_driver(Ps4000Library::new()?)
+    }
+}
+
+impl<D: Ps4000Driver + 'static> PS4262<D> {
+    pub fn with_driver(driver: D) -> Result<Self> {
+        Ps4000Device::with_driver(driver)?.try_into()
+    }
+
+    pub fn with_driver_and_serial(driver: D, serial: &str) -> Result<Self> {
+        Ps4000Device::with_driver_and_serial(driver, serial)?.try_into()
+    }
+
+    pub fn open_async_with_driver(driver: D) -> Result<PendingOpen<D, Self>> {
+        driver.open_unit_async()?;
+        Ok(PendingOpen::new(driver))
+    }
+
+    pub fn into_inner(self) -> Ps4000Device<D> {
+        self.0
+    }
+}
+
+impl<D: Ps4000Driver + 'static> TryFrom<Ps4000Device<D>> for PS4262<D> {
+    type Error = Error;
+
+    fn try_from(device: Ps4000Device<D>) -> Result<Self> {
+        if device.variant != Variant::PS4262 {
+            return Err(Error::UnsupportedVariant(format!(
+                "expected PicoScope 4262, found {}",
+                device.variant
+            )));
         }
+        Ok(Self(device))
+    }
+}
+
+impl<D: Ps4000Driver + 'static> Deref for PS4262<D> {
+    type Target = Ps4000Device<D>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<D: Ps4000Driver + 'static> DerefMut for PS4262<D> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
     }
 }
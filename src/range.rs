@@ -4,17 +4,16 @@
  * Created Date: 15/11/2023
  * Author: Shun Suzuki
  * -----
- * Last Modified: 16/11/2023
+ * Last Modified: 18/10/2026
  * Modified By: Shun Suzuki (suzuki@hapis.k.u-tokyo.ac.jp)
  * -----
  * Copyright (c) 2023 Shun Suzuki. All rights reserved.
  *
  */
 
-use pico_common::PicoStatus;
 use pico_sys_dynamic::ps4000::*;
 
-use crate::attenuation::Attenuation;
+use crate::{attenuation::Attenuation, error::Error, Result};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Range {
@@ -34,7 +33,7 @@ pub enum Range {
 }
 
 impl Range {
-    pub(crate) fn into_range(&self, attenuation: Attenuation) -> Result<enPS4000Range, PicoStatus> {
+    pub(crate) fn into_range(self, attenuation: Attenuation) -> Result<enPS4000Range> {
         match attenuation {
             Attenuation::X1 => match self {
                 Range::Range10mv => Ok(enPS4000Range_PS4000_10MV),
@@ -52,9 +51,9 @@ impl Range {
                 Range::Range100v => Ok(enPS4000Range_PS4000_100V),
             },
             Attenuation::X10 => match self {
-                Range::Range10mv | Range::Range20mv | Range::Range50mv => {
-                    Err(PicoStatus::INVALID_VOLTAGE_RANGE)
-                }
+                Range::Range10mv | Range::Range20mv | Range::Range50mv => Err(
+                    Error::InvalidConfig(format!("{:?} is not available with X10 probe", self)),
+                ),
                 Range::Range100mv => Ok(enPS4000Range_PS4000_10MV),
                 Range::Range200mv => Ok(enPS4000Range_PS4000_20MV),
                 Range::Range500mv => Ok(enPS4000Range_PS4000_50MV),
@@ -71,23 +70,27 @@ impl Range {
 }
 
 impl Range {
-    #[allow(non_upper_case_globals)]
-    pub fn mv(&self, attenuation: Attenuation) -> Result<i32, PicoStatus> {
-        match self.into_range(attenuation)? {
-            enPS4000Range_PS4000_10MV => Ok(10),
-            enPS4000Range_PS4000_20MV => Ok(20),
-            enPS4000Range_PS4000_50MV => Ok(50),
-            enPS4000Range_PS4000_100MV => Ok(100),
-            enPS4000Range_PS4000_200MV => Ok(200),
-            enPS4000Range_PS4000_500MV => Ok(500),
-            enPS4000Range_PS4000_1V => Ok(1000),
-            enPS4000Range_PS4000_2V => Ok(2000),
-            enPS4000Range_PS4000_5V => Ok(5000),
-            enPS4000Range_PS4000_10V => Ok(10_000),
-            enPS4000Range_PS4000_20V => Ok(20_000),
-            enPS4000Range_PS4000_50V => Ok(50_000),
-            enPS4000Range_PS4000_100V => Ok(100_000),
-            _ => unreachable!(),
-        }
+    pub fn mv(&self, attenuation: Attenuation) -> Result<i32> {
+        Ok(range_mv(self.into_range(attenuation)?).unwrap())
+    }
+}
+
+#[allow(non_upper_case_globals)]
+pub(crate) fn range_mv(range: enPS4000Range) -> Option<i32> {
+    match range {
+        enPS4000Range_PS4000_10MV => Some(10),
+        enPS4000Range_PS4000_20MV => Some(20),
+        enPS4000Range_PS4000_50MV => Some(50),
+        enPS4000Range_PS4000_100MV => Some(100),
+        enPS4000Range_PS4000_200MV => Some(200),
+        enPS4000Range_PS4000_500MV => Some(500),
+        enPS4000Range_PS4000_1V => Some(1000),
+        enPS4000Range_PS4000_2V => Some(2000),
+        enPS4000Range_PS4000_5V => Some(5000),
+        enPS4000Range_PS4000_10V => Some(10_000),
+        enPS4000Range_PS4000_20V => Some(20_000),
+        enPS4000Range_PS4000_50V => Some(50_000),
+        enPS4000Range_PS4000_100V => Some(100_000),
+        _ => None,
     }
 }
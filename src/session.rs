@@ -0,0 +1,191 @@
+/*
+ * File: session.rs
+ * Project: src
+ * Created Date: 18/10/2026
+ * Author: Shun Suzuki
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: Shun Suzuki (suzuki@hapis.k.u-tokyo.ac.jp)
+ * -----
+ * Copyright (c) 2026 Shun Suzuki. All rights reserved.
+ *
+ */
+
+use std::time::Duration;
+
+use crate::{
+    block_data::BlockData,
+    buffer_pool::BufferPool,
+    captured_block::BlockReader,
+    channel::{Channel, ChannelConfig},
+    downsampling::Downsampling,
+    driver::Ps4000Driver,
+    ps4000::Ps4000Device,
+    timebase::Timebase,
+    trigger::TriggerConfig,
+    Result,
+};
+
+/// Repeated block captures with a fixed configuration, returned by [`Ps4000Device::block_session`].
+///
+/// The channels, timebase and trigger are sent to the driver before the first capture
+/// and again only after they are changed through the session.
+/// Buffers are taken from the device's [`BufferPool`], or from one owned by the session if it has none.
+pub struct BlockSession<'a, D: Ps4000Driver + 'static> {
+    device: &'a mut Ps4000Device<D>,
+    sample_count: u32,
+    sample_rate: u32,
+    trigger: Option<TriggerConfig>,
+    pool: BufferPool,
+    // `None` until the channels and timebase are applied
+    applied: Option<(Timebase, BlockReader<D>)>,
+    trigger_applied: bool,
+}
+
+impl<D: Ps4000Driver + 'static> Ps4000Device<D> {
+    /// Starts a session of repeated block captures of `sample_count` samples at `sample_rate`.
+    pub fn block_session(&mut self, sample_count: u32, sample_rate: u32) -> BlockSession<'_, D> {
+        BlockSession {
+            device: self,
+            sample_count,
+            sample_rate,
+            trigger: None,
+            pool: BufferPool::new(),
+            applied: None,
+            trigger_applied: false,
+        }
+    }
+}
+
+impl<D: Ps4000Driver + 'static> BlockSession<'_, D> {
+    /// Sets the trigger of every capture; without it, captures start immediately.
+    pub fn with_trigger(mut self, cond: impl Into<TriggerConfig>) -> Self {
+        self.set_trigger(Some(cond.into()));
+        self
+    }
+
+    pub fn set_trigger(&mut self, cond: Option<TriggerConfig>) {
+        self.trigger = cond;
+        self.trigger_applied = false;
+    }
+
+    pub fn set_sample_count(&mut self, sample_count: u32) {
+        self.sample_count = sample_count;
+        self.applied = None;
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: u32) {
+        self.sample_rate = sample_rate;
+        self.applied = None;
+    }
+
+    /// Returns the settings of `channel`; they are applied before the next capture.
+    pub fn channel_mut(&mut self, channel: Channel) -> Result<&mut ChannelConfig> {
+        self.applied = None;
+        self.device.channel_mut(channel)
+    }
+
+    pub fn set_downsampling(&mut self, downsampling: Downsampling) -> Result<()> {
+        self.device.set_downsampling(downsampling)
+    }
+
+    pub fn device(&self) -> &Ps4000Device<D> {
+        self.device
+    }
+
+    /// The timebase of the captures, once the configuration has been applied.
+    pub fn timebase(&self) -> Option<Timebase> {
+        self.applied.as_ref().map(|(timebase, _)| *timebase)
+    }
+
+    /// Captures a block, blocking until the trigger fires.
+    pub fn capture(&mut self) -> Result<BlockData> {
+        self.capture_with(None)
+    }
+
+    /// Captures a block, or stops the capture and returns [`crate::Error::Timeout`] if the trigger does not fire within `timeout`.
+    pub fn capture_timeout(&mut self, timeout: Duration) -> Result<BlockData> {
+        self.capture_with(Some(timeout))
+    }
+
+    fn capture_with(&mut self, timeout: Option<Duration>) -> Result<BlockData> {
+        let (timebase, reader) = match &mut self.applied {
+            Some(applied) => applied,
+            None => {
+                self.device.update_channels()?;
+                let timebase = self
+                    .device
+                    .capture_timebase(self.sample_rate, self.sample_count)?;
+                let mut reader = self.device.block_reader(self.sample_count, timebase)?;
+                reader.pool.get_or_insert_with(|| self.pool.clone());
+                // thresholds and pulse widths depend on the ranges and the sampling interval
+                self.trigger_applied = false;
+                self.applied.insert((timebase, reader))
+            }
+        };
+        if !self.trigger_applied {
+            self.device
+                .apply_trigger(self.trigger.as_ref(), timebase.interval_ns)?;
+            self.trigger_applied = true;
+        }
+
+        self.device.run_block_and_wait(
+            self.sample_count,
+            *timebase,
+            self.trigger.as_ref(),
+            timeout,
+        )?;
+        reader.read(0, self.sample_count, self.device.downsampling())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        range::Range,
+        simulated::SimulatedPs4262,
+        trigger::{ThresholdDirection, Trigger},
+    };
+
+    const SET_CHANNEL: &str = "ps4000SetChannel";
+    const SET_TRIGGER: &str = "ps4000SetSimpleTrigger";
+    const GET_TIMEBASE: &str = "ps4000GetTimebase";
+
+    fn calls(session: &BlockSession<'_, SimulatedPs4262>) -> [usize; 3] {
+        let driver = session.device().driver();
+        [SET_CHANNEL, SET_TRIGGER, GET_TIMEBASE].map(|f| driver.calls(f))
+    }
+
+    #[test]
+    fn settings_are_applied_again_only_after_they_change() {
+        let mut ps = Ps4000Device::with_driver(SimulatedPs4262::new()).unwrap();
+        let trigger = Trigger::new(Channel::A, ThresholdDirection::Rising, 0.0);
+        let mut session = ps.block_session(1000, 1_000_000).with_trigger(trigger);
+
+        session.capture().unwrap();
+        let [channel, trigger, timebase] = calls(&session);
+        assert!(channel > 0 && trigger > 0 && timebase > 0);
+
+        for _ in 0..3 {
+            session.capture().unwrap();
+        }
+        assert_eq!(calls(&session), [channel, trigger, timebase]);
+
+        session.channel_mut(Channel::A).unwrap().range = Range::Range2v;
+        session.capture().unwrap();
+        let [c, t, tb] = calls(&session);
+        assert!(c > channel && tb > timebase);
+        // thresholds depend on the range, so the trigger is applied again as well
+        assert!(t > trigger);
+        let (channel, trigger, timebase) = (c, t, tb);
+
+        session.set_trigger(Some(
+            Trigger::new(Channel::A, ThresholdDirection::Falling, 0.0).into(),
+        ));
+        session.capture().unwrap();
+        let [c, t, tb] = calls(&session);
+        assert_eq!((c, tb), (channel, timebase));
+        assert!(t > trigger);
+    }
+}
@@ -0,0 +1,424 @@
+/*
+ * File: sig_gen.rs
+ * Project: src
+ * Created Date: 18/10/2026
+ * Author: Shun Suzuki
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: Shun Suzuki (suzuki@hapis.k.u-tokyo.ac.jp)
+ * -----
+ * Copyright (c) 2026 Shun Suzuki. All rights reserved.
+ *
+ */
+
+use std::{f64::consts::PI, time::Duration};
+
+use pico_sys_dynamic::ps4000::*;
+
+use crate::{driver::Ps4000Driver, Error, Result};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WaveType {
+    Sine,
+    Square,
+    Triangle,
+    RampUp,
+    RampDown,
+    Sinc,
+    Gaussian,
+    HalfSine,
+    DcVoltage,
+    WhiteNoise,
+}
+
+impl From<WaveType> for WAVE_TYPE {
+    fn from(value: WaveType) -> Self {
+        match value {
+            WaveType::Sine => enWaveType_PS4000_SINE,
+            WaveType::Square => enWaveType_PS4000_SQUARE,
+            WaveType::Triangle => enWaveType_PS4000_TRIANGLE,
+            WaveType::RampUp => enWaveType_PS4000_RAMP_UP,
+            WaveType::RampDown => enWaveType_PS4000_RAMP_DOWN,
+            WaveType::Sinc => enWaveType_PS4000_SINC,
+            WaveType::Gaussian => enWaveType_PS4000_GAUSSIAN,
+            WaveType::HalfSine => enWaveType_PS4000_HALF_SINE,
+            WaveType::DcVoltage => enWaveType_PS4000_DC_VOLTAGE,
+            WaveType::WhiteNoise => enWaveType_PS4000_WHITE_NOISE,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SweepType {
+    Up,
+    Down,
+    UpDown,
+    DownUp,
+}
+
+impl From<SweepType> for SWEEP_TYPE {
+    fn from(value: SweepType) -> Self {
+        match value {
+            SweepType::Up => enSweepType_UP,
+            SweepType::Down => enSweepType_DOWN,
+            SweepType::UpDown => enSweepType_UPDOWN,
+            SweepType::DownUp => enSweepType_DOWNUP,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SigGenTrigType {
+    Rising,
+    Falling,
+    GateHigh,
+    GateLow,
+}
+
+impl From<SigGenTrigType> for SIGGEN_TRIG_TYPE {
+    fn from(value: SigGenTrigType) -> Self {
+        match value {
+            SigGenTrigType::Rising => enSigGenTrigType_SIGGEN_RISING,
+            SigGenTrigType::Falling => enSigGenTrigType_SIGGEN_FALLING,
+            SigGenTrigType::GateHigh => enSigGenTrigType_SIGGEN_GATE_HIGH,
+            SigGenTrigType::GateLow => enSigGenTrigType_SIGGEN_GATE_LOW,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SigGenTrigSource {
+    None,
+    ScopeTrig,
+    AuxIn,
+    ExtIn,
+    SoftTrig,
+}
+
+impl From<SigGenTrigSource> for SIGGEN_TRIG_SOURCE {
+    fn from(value: SigGenTrigSource) -> Self {
+        match value {
+            SigGenTrigSource::None => enSigGenTrigSource_SIGGEN_NONE,
+            SigGenTrigSource::ScopeTrig => enSigGenTrigSource_SIGGEN_SCOPE_TRIG,
+            SigGenTrigSource::AuxIn => enSigGenTrigSource_SIGGEN_AUX_IN,
+            SigGenTrigSource::ExtIn => enSigGenTrigSource_SIGGEN_EXT_IN,
+            SigGenTrigSource::SoftTrig => enSigGenTrigSource_SIGGEN_SOFT_TRIG,
+        }
+    }
+}
+
+/// Settings of the built-in waveforms of the signal generator.
+///
+/// The output runs continuously at `frequency` unless a sweep, shots or a trigger is set.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BuiltInSignal {
+    pub(crate) wave_type: WaveType,
+    pub(crate) offset_uv: i32,
+    pub(crate) pk_to_pk_uv: u32,
+    pub(crate) start_frequency: f32,
+    pub(crate) stop_frequency: f32,
+    pub(crate) increment: f32,
+    pub(crate) dwell_time: f32,
+    pub(crate) sweep_type: SweepType,
+    pub(crate) shots: u32,
+    pub(crate) sweeps: u32,
+    pub(crate) trigger_type: SigGenTrigType,
+    pub(crate) trigger_source: SigGenTrigSource,
+    pub(crate) ext_in_threshold: i16,
+}
+
+impl BuiltInSignal {
+    pub fn new(wave_type: WaveType, frequency: f32, pk_to_pk_uv: u32) -> Self {
+        Self {
+            wave_type,
+            offset_uv: 0,
+            pk_to_pk_uv,
+            start_frequency: frequency,
+            stop_frequency: frequency,
+            increment: 0.,
+            dwell_time: 0.,
+            sweep_type: SweepType::Up,
+            shots: 0,
+            sweeps: 0,
+            trigger_type: SigGenTrigType::Rising,
+            trigger_source: SigGenTrigSource::None,
+            ext_in_threshold: 0,
+        }
+    }
+
+    pub fn with_offset_uv(mut self, offset_uv: i32) -> Self {
+        self.offset_uv = offset_uv;
+        self
+    }
+
+    /// Sweeps from the start frequency to `stop_frequency`, stepping by `increment` Hz every `dwell_time` seconds.
+    pub fn with_sweep(
+        mut self,
+        stop_frequency: f32,
+        increment: f32,
+        dwell_time: f32,
+        sweep_type: SweepType,
+    ) -> Self {
+        self.stop_frequency = stop_frequency;
+        self.increment = increment;
+        self.dwell_time = dwell_time;
+        self.sweep_type = sweep_type;
+        self
+    }
+
+    /// Outputs `shots` cycles each time the signal generator is triggered.
+    pub fn with_shots(mut self, shots: u32) -> Self {
+        self.shots = shots;
+        self
+    }
+
+    /// Outputs `sweeps` sweeps each time the signal generator is triggered.
+    pub fn with_sweeps(mut self, sweeps: u32) -> Self {
+        self.sweeps = sweeps;
+        self
+    }
+
+    pub fn with_trigger(mut self, trigger_type: SigGenTrigType, source: SigGenTrigSource) -> Self {
+        self.trigger_type = trigger_type;
+        self.trigger_source = source;
+        self
+    }
+
+    /// Sets the threshold in ADC counts used when triggered from the EXT input.
+    pub fn with_ext_in_threshold(mut self, ext_in_threshold: i16) -> Self {
+        self.ext_in_threshold = ext_in_threshold;
+        self
+    }
+}
+
+/// One period of a waveform for the arbitrary waveform generator.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ArbitraryWaveform {
+    samples: Vec<f64>,
+    period: Duration,
+}
+
+impl ArbitraryWaveform {
+    /// Samples `f(t)` at `len` points over one `period`, with `t` in seconds.
+    pub fn from_fn(period: Duration, len: usize, f: impl Fn(f64) -> f64) -> Self {
+        let dt = period.as_secs_f64() / len as f64;
+        Self {
+            samples: (0..len).map(|i| f(i as f64 * dt)).collect(),
+            period,
+        }
+    }
+
+    /// A linear sweep from `start_frequency` to `stop_frequency` over one period.
+    pub fn chirp(period: Duration, len: usize, start_frequency: f64, stop_frequency: f64) -> Self {
+        let rate = (stop_frequency - start_frequency) / period.as_secs_f64();
+        Self::from_fn(period, len, |t| {
+            (2. * PI * (start_frequency * t + rate * t * t / 2.)).sin()
+        })
+    }
+
+    /// `cycles` cycles of a sine at `frequency` followed by silence for the rest of the period.
+    pub fn tone_burst(period: Duration, len: usize, frequency: f64, cycles: u32) -> Self {
+        let burst = cycles as f64 / frequency;
+        Self::from_fn(period, len, |t| {
+            if t < burst {
+                (2. * PI * frequency * t).sin()
+            } else {
+                0.
+            }
+        })
+    }
+
+    /// `pulses` pulses evenly spread over the period, each `width` long and shaped by `pulse(t)`
+    /// with `t` in seconds from the start of the pulse.
+    pub fn pulse_train(
+        period: Duration,
+        len: usize,
+        pulses: u32,
+        width: Duration,
+        pulse: impl Fn(f64) -> f64,
+    ) -> Self {
+        let interval = period.as_secs_f64() / pulses.max(1) as f64;
+        let width = width.as_secs_f64();
+        Self::from_fn(period, len, |t| {
+            let t = t % interval;
+            if t < width {
+                pulse(t)
+            } else {
+                0.
+            }
+        })
+    }
+
+    pub fn samples(&self) -> &[f64] {
+        &self.samples
+    }
+
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    /// The repetition frequency of the waveform.
+    pub fn frequency(&self) -> f64 {
+        1. / self.period.as_secs_f64()
+    }
+}
+
+/// The signal generator of a [`crate::Ps4000Device`], with the limits of its [`crate::Variant`].
+pub struct SignalGenerator<'a, D> {
+    pub(crate) driver: &'a D,
+    pub(crate) handle: i16,
+    pub(crate) max_frequency: f32,
+    pub(crate) max_voltage_uv: u32,
+}
+
+impl<'a, D: Ps4000Driver> SignalGenerator<'a, D> {
+    pub const MIN_FREQUENCY: f32 = 0.03;
+
+    pub fn max_frequency(&self) -> f32 {
+        self.max_frequency
+    }
+
+    /// Maximum output voltage including the offset.
+    pub fn max_voltage_uv(&self) -> u32 {
+        self.max_voltage_uv
+    }
+
+    fn validate_output(&self, offset_uv: i32, pk_to_pk_uv: u32) -> Result<()> {
+        if offset_uv.unsigned_abs() as u64 + pk_to_pk_uv as u64 / 2 > self.max_voltage_uv as u64 {
+            return Err(Error::InvalidConfig(format!(
+                "offset {} uV with {} uV peak-to-peak exceeds the output range of ±{} uV",
+                offset_uv, pk_to_pk_uv, self.max_voltage_uv
+            )));
+        }
+        Ok(())
+    }
+
+    fn validate_frequency(&self, frequency: f32) -> Result<()> {
+        if !(Self::MIN_FREQUENCY..=self.max_frequency).contains(&frequency) {
+            return Err(Error::InvalidConfig(format!(
+                "frequency {} Hz is out of range",
+                frequency
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn set_built_in(&self, signal: &BuiltInSignal) -> Result<()> {
+        self.validate_output(signal.offset_uv, signal.pk_to_pk_uv)?;
+        if signal.wave_type != WaveType::DcVoltage {
+            self.validate_frequency(signal.start_frequency)?;
+            self.validate_frequency(signal.stop_frequency)?;
+        }
+        if signal.trigger_source != SigGenTrigSource::None
+            && signal.shots == 0
+            && signal.sweeps == 0
+        {
+            return Err(Error::InvalidConfig(
+                "a triggered signal generator needs a number of shots or sweeps".to_string(),
+            ));
+        }
+
+        let operation = match signal.wave_type {
+            WaveType::WhiteNoise => enPS4000OperationTypes_PS4000_WHITENOISE,
+            _ => enPS4000OperationTypes_PS4000_OP_NONE,
+        };
+        self.driver.set_sig_gen_built_in(
+            self.handle,
+            signal.offset_uv,
+            signal.pk_to_pk_uv,
+            signal.wave_type.into(),
+            signal.start_frequency,
+            signal.stop_frequency,
+            signal.increment,
+            signal.dwell_time,
+            signal.sweep_type.into(),
+            operation,
+            signal.shots,
+            signal.sweeps,
+            signal.trigger_type.into(),
+            signal.trigger_source.into(),
+            signal.ext_in_threshold,
+        )
+    }
+
+    /// Triggers the signal generator when its trigger source is [`SigGenTrigSource::SoftTrig`].
+    ///
+    /// With a gated trigger type, `state` sets the gate.
+    pub fn software_trigger(&self, state: bool) -> Result<()> {
+        self.driver.sig_gen_software_control(self.handle, state)
+    }
+
+    /// Outputs `samples` repeatedly at `frequency` repetitions per second.
+    ///
+    /// The samples are scaled so that the largest magnitude reaches full scale, which corresponds to `pk_to_pk_uv`.
+    pub fn set_arbitrary(&self, samples: &[f64], frequency: f64, pk_to_pk_uv: u32) -> Result<()> {
+        self.validate_output(0, pk_to_pk_uv)?;
+        // non-finite and non-positive frequencies fall outside the range too
+        self.validate_frequency(frequency as f32)?;
+        let (min_value, max_value, min_size, max_size) =
+            self.driver.sig_gen_arbitrary_min_max_values(self.handle)?;
+        if !(min_size as usize..=max_size as usize).contains(&samples.len()) {
+            return Err(Error::InvalidConfig(format!(
+                "arbitrary waveform has {} samples but must have between {} and {}",
+                samples.len(),
+                min_size,
+                max_size
+            )));
+        }
+        if samples.iter().any(|x| !x.is_finite()) {
+            return Err(Error::InvalidConfig(
+                "arbitrary waveform contains a non-finite sample".to_string(),
+            ));
+        }
+
+        let peak = samples.iter().fold(0f64, |acc, x| acc.max(x.abs()));
+        let full_scale = (max_value as f64).min(-(min_value as f64));
+        let waveform = samples
+            .iter()
+            .map(|&x| {
+                if peak == 0. {
+                    0
+                } else {
+                    (x / peak * full_scale).round() as i16
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let delta_phase = self.driver.sig_gen_frequency_to_phase(
+            self.handle,
+            frequency,
+            enIndexMode_SINGLE,
+            waveform.len() as _,
+        )?;
+        self.driver.set_sig_gen_arbitrary(
+            self.handle,
+            0,
+            pk_to_pk_uv,
+            delta_phase,
+            delta_phase,
+            0,
+            0,
+            &waveform,
+            enSweepType_UP,
+            enPS4000OperationTypes_PS4000_OP_NONE,
+            enIndexMode_SINGLE,
+            0,
+            0,
+            enSigGenTrigType_SIGGEN_RISING,
+            enSigGenTrigSource_SIGGEN_NONE,
+            0,
+        )
+    }
+
+    pub fn set_arbitrary_waveform(
+        &self,
+        waveform: &ArbitraryWaveform,
+        pk_to_pk_uv: u32,
+    ) -> Result<()> {
+        self.set_arbitrary(waveform.samples(), waveform.frequency(), pk_to_pk_uv)
+    }
+
+    pub fn off(&self) -> Result<()> {
+        self.driver.sig_gen_off(self.handle)
+    }
+}
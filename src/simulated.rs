@@ -0,0 +1,1808 @@
+/*
+ * File: simulated.rs
+ * Project: src
+ * Created Date: 18/10/2026
+ * Author: Shun Suzuki
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: Shun Suzuki (suzuki@hapis.k.u-tokyo.ac.jp)
+ * -----
+ * Copyright (c) 2026 Shun Suzuki. All rights reserved.
+ *
+ */
+
+use std::{
+    collections::HashMap,
+    f64::consts::PI,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use pico_common::{PicoCoupling, PicoInfo, PicoStatus};
+use pico_sys_dynamic::ps4000::{
+    enIndexMode_SINGLE, enPS4000TimeUnits_PS4000_FS, enPS4000TimeUnits_PS4000_MS,
+    enPS4000TimeUnits_PS4000_NS, enPS4000TimeUnits_PS4000_PS, enPS4000TimeUnits_PS4000_S,
+    enPS4000TimeUnits_PS4000_US, enPulseWidthType_PW_TYPE_GREATER_THAN,
+    enPulseWidthType_PW_TYPE_IN_RANGE, enPulseWidthType_PW_TYPE_LESS_THAN,
+    enPulseWidthType_PW_TYPE_NONE, enPulseWidthType_PW_TYPE_OUT_OF_RANGE,
+    enRatioMode_RATIO_MODE_AGGREGATE, enRatioMode_RATIO_MODE_AVERAGE, enRatioMode_RATIO_MODE_NONE,
+    enSweepType_MAX_SWEEP_TYPES, enThresholdDirection_ABOVE, enThresholdDirection_BELOW,
+    enThresholdDirection_ENTER, enThresholdDirection_ENTER_OR_EXIT, enThresholdDirection_EXIT,
+    enThresholdDirection_FALLING, enThresholdDirection_INSIDE, enThresholdDirection_NONE,
+    enThresholdDirection_OUTSIDE, enThresholdDirection_RISING,
+    enThresholdDirection_RISING_OR_FALLING, enThresholdMode_LEVEL,
+    enTriggerState_CONDITION_DONT_CARE, enTriggerState_CONDITION_FALSE,
+    enTriggerState_CONDITION_TRUE, enWaveType_MAX_WAVE_TYPES, INDEX_MODE, PS4000_CHANNEL,
+    PS4000_OPERATION_TYPES, PS4000_RANGE, PS4000_TIME_UNITS, PULSE_WIDTH_TYPE, PWQ_CONDITIONS,
+    RATIO_MODE, SIGGEN_TRIG_SOURCE, SIGGEN_TRIG_TYPE, SWEEP_TYPE, THRESHOLD_DIRECTION,
+    THRESHOLD_MODE, TRIGGER_CHANNEL_PROPERTIES, TRIGGER_CONDITIONS, TRIGGER_STATE, WAVE_TYPE,
+};
+
+use crate::{
+    attenuation::Attenuation,
+    driver::{BlockReady, Ps4000Driver, StreamingReady},
+    error::Error,
+    range::range_mv,
+    trigger::TriggerSource,
+    variant::Variant,
+    Channel, Result,
+};
+
+const HANDLE: i16 = 1;
+const OPEN_PROGRESS_STEP: i16 = 25;
+const MAX_CHANNELS: usize = 4;
+const MAX_SAMPLES: i32 = 16_000_000;
+const MAX_TRIGGER_SEARCH: usize = 1 << 24;
+// Samples searched for a trigger at a time while holding the state lock.
+const TRIGGER_SEARCH_STEP: usize = 1 << 14;
+const AWG_DAC_FREQUENCY: f64 = 192_000.0;
+const AWG_MIN_SIZE: u32 = 10;
+const AWG_MAX_SIZE: u32 = 4_096;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Waveform {
+    Dc,
+    Sine,
+    Square,
+    Pulse { width: Duration },
+    Noise,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Signal {
+    pub(crate) waveform: Waveform,
+    pub(crate) frequency: f64,
+    pub(crate) amplitude_mv: f64,
+    pub(crate) offset_mv: f64,
+    pub(crate) phase: f64,
+    pub(crate) noise_mv: f64,
+}
+
+impl Signal {
+    fn new(waveform: Waveform, frequency: f64, amplitude_mv: f64) -> Self {
+        Self {
+            waveform,
+            frequency,
+            amplitude_mv,
+            offset_mv: 0.0,
+            phase: 0.0,
+            noise_mv: 0.0,
+        }
+    }
+
+    pub fn dc(value_mv: f64) -> Self {
+        Self::new(Waveform::Dc, 0.0, 0.0).with_offset_mv(value_mv)
+    }
+
+    pub fn sine(frequency: f64, amplitude_mv: f64) -> Self {
+        Self::new(Waveform::Sine, frequency, amplitude_mv)
+    }
+
+    pub fn square(frequency: f64, amplitude_mv: f64) -> Self {
+        Self::new(Waveform::Square, frequency, amplitude_mv)
+    }
+
+    pub fn pulse(frequency: f64, width: Duration, amplitude_mv: f64) -> Self {
+        Self::new(Waveform::Pulse { width }, frequency, amplitude_mv)
+    }
+
+    /// Gaussian noise whose standard deviation is `amplitude_mv`.
+    pub fn noise(amplitude_mv: f64) -> Self {
+        Self::new(Waveform::Noise, 0.0, amplitude_mv)
+    }
+
+    pub fn with_offset_mv(mut self, offset_mv: f64) -> Self {
+        self.offset_mv = offset_mv;
+        self
+    }
+
+    pub fn with_phase(mut self, phase: f64) -> Self {
+        self.phase = phase;
+        self
+    }
+
+    /// Adds gaussian noise whose standard deviation is `noise_mv`.
+    pub fn with_noise_mv(mut self, noise_mv: f64) -> Self {
+        self.noise_mv = noise_mv;
+        self
+    }
+
+    fn value_mv(&self, t: f64, mut noise: impl FnMut() -> f64) -> f64 {
+        let cycle = self.frequency * t + self.phase / (2.0 * PI);
+        let v = match self.waveform {
+            Waveform::Dc => 0.0,
+            Waveform::Sine => self.amplitude_mv * (2.0 * PI * cycle).sin(),
+            Waveform::Square => {
+                if cycle.rem_euclid(1.0) < 0.5 {
+                    self.amplitude_mv
+                } else {
+                    -self.amplitude_mv
+                }
+            }
+            Waveform::Pulse { width } => {
+                if cycle.rem_euclid(1.0) < width.as_secs_f64() * self.frequency {
+                    self.amplitude_mv
+                } else {
+                    0.0
+                }
+            }
+            Waveform::Noise => self.amplitude_mv * noise(),
+        };
+        let v = if self.noise_mv != 0.0 {
+            v + self.noise_mv * noise()
+        } else {
+            v
+        };
+        self.offset_mv + v
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ChannelState {
+    enabled: bool,
+    range_mv: i32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ChannelTrigger {
+    mode: THRESHOLD_MODE,
+    upper: i16,
+    upper_hysteresis: u16,
+    lower: i16,
+    lower_hysteresis: u16,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Condition {
+    channels: [TRIGGER_STATE; MAX_CHANNELS],
+    external: TRIGGER_STATE,
+    aux: TRIGGER_STATE,
+    pulse_width_qualifier: TRIGGER_STATE,
+}
+
+impl Condition {
+    // Whether every input the condition uses exists on `variant`.
+    fn is_available(&self, variant: Variant) -> bool {
+        let sources = [
+            TriggerSource::A,
+            TriggerSource::B,
+            TriggerSource::C,
+            TriggerSource::D,
+        ]
+        .into_iter()
+        .zip(self.channels)
+        .chain([
+            (TriggerSource::External, self.external),
+            (TriggerSource::Aux, self.aux),
+        ]);
+        sources.into_iter().all(|(source, state)| {
+            state == enTriggerState_CONDITION_DONT_CARE || variant.has_trigger_source(source)
+        })
+    }
+
+    fn is_active(&self) -> bool {
+        self.channels
+            .iter()
+            .chain([&self.external, &self.aux, &self.pulse_width_qualifier])
+            .any(|&s| s != enTriggerState_CONDITION_DONT_CARE)
+    }
+
+    #[allow(non_upper_case_globals)]
+    fn holds(&self, channels: [bool; MAX_CHANNELS], pulse_width_qualifier: bool) -> bool {
+        // There is no signal on EXT and AUX IN, so they never fire.
+        let sources = self.channels.iter().zip(channels).chain([
+            (&self.external, false),
+            (&self.aux, false),
+            (&self.pulse_width_qualifier, pulse_width_qualifier),
+        ]);
+        self.is_active()
+            && sources.into_iter().all(|(&state, hit)| match state {
+                enTriggerState_CONDITION_TRUE => hit,
+                enTriggerState_CONDITION_FALSE => !hit,
+                _ => true,
+            })
+    }
+}
+
+#[derive(Debug, Clone)]
+struct PulseWidth {
+    conditions: Vec<Condition>,
+    direction: THRESHOLD_DIRECTION,
+    lower: u32,
+    upper: u32,
+    ty: PULSE_WIDTH_TYPE,
+}
+
+impl PulseWidth {
+    #[allow(non_upper_case_globals)]
+    fn qualifies(&self, width: usize) -> bool {
+        let width = width as u32;
+        match self.ty {
+            enPulseWidthType_PW_TYPE_LESS_THAN => width < self.lower,
+            enPulseWidthType_PW_TYPE_GREATER_THAN => width > self.lower,
+            enPulseWidthType_PW_TYPE_IN_RANGE => self.lower < width && width < self.upper,
+            enPulseWidthType_PW_TYPE_OUT_OF_RANGE => width < self.lower || width > self.upper,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct TriggerSetup {
+    properties: [Option<ChannelTrigger>; MAX_CHANNELS],
+    directions: [THRESHOLD_DIRECTION; MAX_CHANNELS],
+    conditions: Vec<Condition>,
+    pulse_width: Option<PulseWidth>,
+    delay: u32,
+    auto_trigger_ms: i32,
+}
+
+impl TriggerSetup {
+    fn new() -> Self {
+        Self {
+            properties: [None; MAX_CHANNELS],
+            directions: [enThresholdDirection_NONE; MAX_CHANNELS],
+            conditions: Vec::new(),
+            pulse_width: None,
+            delay: 0,
+            auto_trigger_ms: 0,
+        }
+    }
+
+    fn enabled(&self) -> bool {
+        self.conditions.iter().any(Condition::is_active)
+    }
+
+    fn uses_pulse_width(&self) -> bool {
+        self.pulse_width.is_some()
+            && self
+                .conditions
+                .iter()
+                .any(|c| c.pulse_width_qualifier != enTriggerState_CONDITION_DONT_CARE)
+    }
+
+    fn uses(&self, ch: usize) -> bool {
+        let pulse_width = self
+            .pulse_width
+            .iter()
+            .filter(|_| self.uses_pulse_width())
+            .flat_map(|pw| pw.conditions.iter());
+        self.conditions
+            .iter()
+            .chain(pulse_width)
+            .any(|c| c.channels[ch] != enTriggerState_CONDITION_DONT_CARE)
+    }
+}
+
+// Tracks the pulse measured by the pulse width qualifier, and which channels fired since it started
+// so that an edge at its start still counts when the pulse ends.
+#[derive(Debug, Clone, Copy, Default)]
+struct Pulse {
+    level: bool,
+    start: Option<usize>,
+    hits: [bool; MAX_CHANNELS],
+}
+
+impl Pulse {
+    // Returns the width of the pulse ending at sample `n`.
+    fn step(&mut self, level: bool, n: usize, hits: [bool; MAX_CHANNELS]) -> Option<usize> {
+        if level && !self.level {
+            self.hits = [false; MAX_CHANNELS];
+        }
+        for (latched, hit) in self.hits.iter_mut().zip(hits) {
+            *latched |= hit;
+        }
+        let width = match (self.level, level) {
+            (true, false) => self.start.take().map(|start| n - start),
+            (false, true) => {
+                self.start = Some(n);
+                None
+            }
+            _ => None,
+        };
+        self.level = level;
+        width
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Scan {
+    detectors: [Detector; MAX_CHANNELS],
+    pulse: Pulse,
+}
+
+// Tracks the threshold crossings of a channel across consecutive samples.
+#[derive(Debug, Clone, Copy, Default)]
+struct Detector {
+    armed_rising: bool,
+    armed_falling: bool,
+    inside: bool,
+}
+
+impl Detector {
+    fn new(trigger: &ChannelTrigger, cur: i16) -> Self {
+        Self {
+            armed_rising: cur < trigger.upper,
+            armed_falling: cur > trigger.upper,
+            inside: trigger.lower < cur && cur < trigger.upper,
+        }
+    }
+
+    #[allow(non_upper_case_globals)]
+    fn step(&mut self, trigger: &ChannelTrigger, direction: THRESHOLD_DIRECTION, cur: i16) -> bool {
+        let upper = trigger.upper as i32;
+        let lower = trigger.lower as i32;
+        let hu = trigger.upper_hysteresis as i32;
+        let hl = trigger.lower_hysteresis as i32;
+        let cur = cur as i32;
+
+        let rose = self.armed_rising && cur >= upper;
+        let fell = self.armed_falling && cur <= upper;
+        if cur >= upper {
+            self.armed_rising = false;
+        } else if cur < upper - hu {
+            self.armed_rising = true;
+        }
+        if cur <= upper {
+            self.armed_falling = false;
+        } else if cur > upper + hu {
+            self.armed_falling = true;
+        }
+
+        let inside = if self.inside {
+            lower <= cur && cur <= upper
+        } else {
+            lower + hl < cur && cur < upper - hu
+        };
+        let entered = !self.inside && inside;
+        let exited = self.inside && !inside;
+        self.inside = inside;
+
+        match trigger.mode {
+            enThresholdMode_LEVEL => match direction {
+                enThresholdDirection_ABOVE => cur > upper,
+                enThresholdDirection_BELOW => cur < upper,
+                enThresholdDirection_RISING => rose,
+                enThresholdDirection_FALLING => fell,
+                enThresholdDirection_RISING_OR_FALLING => rose || fell,
+                _ => false,
+            },
+            _ => match direction {
+                enThresholdDirection_INSIDE => inside,
+                enThresholdDirection_OUTSIDE => !inside,
+                enThresholdDirection_ENTER => entered,
+                enThresholdDirection_EXIT => exited,
+                enThresholdDirection_ENTER_OR_EXIT => entered || exited,
+                _ => false,
+            },
+        }
+    }
+}
+
+struct DataBuffer {
+    max: *mut i16,
+    min: *mut i16,
+    len: usize,
+}
+
+// The pointers are only written while the state lock is held, as the vendor driver does from its own threads.
+unsafe impl Send for DataBuffer {}
+
+struct Segment {
+    samples: Vec<Option<Vec<i16>>>,
+    overflow: i16,
+}
+
+impl Segment {
+    fn len(&self) -> usize {
+        self.samples
+            .iter()
+            .flatten()
+            .map(|data| data.len())
+            .next()
+            .unwrap_or(0)
+    }
+
+    // Downsamples `no_of_samples` samples from `start` by `ratio` and returns the number of values written.
+    #[allow(non_upper_case_globals)]
+    fn copy_into(
+        &self,
+        ch: usize,
+        start: usize,
+        no_of_samples: usize,
+        ratio: usize,
+        mode: RATIO_MODE,
+        buffer: &DataBuffer,
+    ) -> Result<Option<usize>, PicoStatus> {
+        let Some(data) = &self.samples[ch] else {
+            return Ok(None);
+        };
+        if start >= data.len() {
+            return Err(PicoStatus::STARTINDEX_INVALID);
+        }
+        let data = &data[start..start + no_of_samples.min(data.len() - start)];
+        if mode == enRatioMode_RATIO_MODE_NONE {
+            let n = data.len().min(buffer.len);
+            unsafe {
+                std::ptr::copy_nonoverlapping(data.as_ptr(), buffer.max, n);
+            }
+            return Ok(Some(n));
+        }
+
+        let mut n = 0;
+        for (i, group) in data.chunks(ratio).take(buffer.len).enumerate() {
+            let (max, min) = match mode {
+                enRatioMode_RATIO_MODE_AGGREGATE => {
+                    (*group.iter().max().unwrap(), *group.iter().min().unwrap())
+                }
+                _ => {
+                    let sum: i64 = group.iter().map(|&x| x as i64).sum();
+                    ((sum / group.len() as i64) as i16, 0)
+                }
+            };
+            unsafe {
+                *buffer.max.add(i) = max;
+                if mode == enRatioMode_RATIO_MODE_AGGREGATE && !buffer.min.is_null() {
+                    *buffer.min.add(i) = min;
+                }
+            }
+            n = i + 1;
+        }
+        Ok(Some(n))
+    }
+}
+
+#[allow(non_upper_case_globals)]
+fn check_ratio_mode(mode: RATIO_MODE) -> Result<(), PicoStatus> {
+    match mode {
+        enRatioMode_RATIO_MODE_NONE
+        | enRatioMode_RATIO_MODE_AGGREGATE
+        | enRatioMode_RATIO_MODE_AVERAGE => Ok(()),
+        _ => Err(PicoStatus::RATIO_MODE_NOT_SUPPORTED),
+    }
+}
+
+// Progress of the trigger search of the segment being captured.
+struct Search {
+    scan: Scan,
+    next: usize,
+    horizon: usize,
+}
+
+// A block capture whose trigger search has not finished yet.
+struct Run {
+    pre: usize,
+    post: usize,
+    dt: f64,
+    segments: Vec<Segment>,
+    search: Option<Search>,
+}
+
+enum Capture {
+    Running(Run),
+    // waits for a trigger that never fires
+    Waiting,
+    Done(Vec<Segment>),
+}
+
+struct Streaming {
+    dt: f64,
+    started: Instant,
+    produced: usize,
+    pre: usize,
+    post: usize,
+    auto_stop: bool,
+    triggered_at: Option<usize>,
+    scan: Scan,
+    stopped: bool,
+}
+
+struct State {
+    open: bool,
+    variant: Variant,
+    signals: [Signal; MAX_CHANNELS],
+    probes: [Attenuation; MAX_CHANNELS],
+    channels: [ChannelState; MAX_CHANNELS],
+    trigger: TriggerSetup,
+    buffers: [Option<DataBuffer>; MAX_CHANNELS],
+    bulk_buffers: HashMap<(usize, u16), DataBuffer>,
+    segments: u16,
+    no_of_captures: u16,
+    capture: Option<Capture>,
+    // incremented by each block capture, so that a callback thread notices it has been replaced
+    run: u64,
+    stream: Option<Streaming>,
+    clock: f64,
+    captures: u64,
+    seed: u64,
+    serial: String,
+    opening: Option<i16>,
+    // number of calls of each driver function on the opened unit
+    calls: HashMap<&'static str, usize>,
+}
+
+/// A stand-in for a PicoScope 4262 that produces synthetic waveforms.
+///
+/// It reports itself as another model of the series when created with [`SimulatedPs4262::with_variant`].
+/// Signals are given at the probe tip; the probe attenuation set with [`SimulatedPs4262::with_probe`]
+/// is applied before digitizing, so a matching [`Attenuation`] on the channel recovers the original voltage.
+/// Signal generator settings are validated but produce no output.
+pub struct SimulatedPs4262 {
+    state: Arc<Mutex<State>>,
+}
+
+impl SimulatedPs4262 {
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(State {
+                open: false,
+                variant: Variant::PS4262,
+                signals: [
+                    Signal::sine(1_000.0, 1_000.0),
+                    Signal::square(1_000.0, 1_000.0),
+                    Signal::sine(1_000.0, 1_000.0),
+                    Signal::square(1_000.0, 1_000.0),
+                ],
+                probes: [Attenuation::X1; MAX_CHANNELS],
+                channels: [ChannelState {
+                    enabled: false,
+                    range_mv: 5_000,
+                }; MAX_CHANNELS],
+                trigger: TriggerSetup::new(),
+                buffers: Default::default(),
+                bulk_buffers: HashMap::new(),
+                segments: 1,
+                no_of_captures: 1,
+                capture: None,
+                run: 0,
+                stream: None,
+                clock: 0.0,
+                captures: 0,
+                seed: 0x5eed,
+                serial: "SIM00/0001".to_string(),
+                opening: None,
+                calls: HashMap::new(),
+            })),
+        }
+    }
+
+    pub fn with_signal(self, channel: Channel, signal: Signal) -> Self {
+        self.set_signal(channel, signal);
+        self
+    }
+
+    pub fn with_probe(self, channel: Channel, attenuation: Attenuation) -> Self {
+        self.set_probe(channel, attenuation);
+        self
+    }
+
+    pub fn with_seed(self, seed: u64) -> Self {
+        self.state.lock().unwrap().seed = seed;
+        self
+    }
+
+    pub fn with_variant(self, variant: Variant) -> Self {
+        self.state.lock().unwrap().variant = variant;
+        self
+    }
+
+    pub fn with_serial(self, serial: impl Into<String>) -> Self {
+        self.state.lock().unwrap().serial = serial.into();
+        self
+    }
+
+    pub fn set_signal(&self, channel: Channel, signal: Signal) {
+        self.state.lock().unwrap().signals[PS4000_CHANNEL::from(channel) as usize] = signal;
+    }
+
+    pub fn set_probe(&self, channel: Channel, attenuation: Attenuation) {
+        self.state.lock().unwrap().probes[PS4000_CHANNEL::from(channel) as usize] = attenuation;
+    }
+
+    fn state(
+        &self,
+        handle: i16,
+        function: &'static str,
+    ) -> Result<std::sync::MutexGuard<'_, State>> {
+        let mut state = self.state.lock().unwrap();
+        if !state.open || handle != HANDLE {
+            return Err(Error::driver(function, PicoStatus::INVALID_HANDLE));
+        }
+        *state.calls.entry(function).or_default() += 1;
+        Ok(state)
+    }
+
+    #[cfg(test)]
+    pub(crate) fn calls(&self, function: &str) -> usize {
+        self.state
+            .lock()
+            .unwrap()
+            .calls
+            .get(function)
+            .copied()
+            .unwrap_or(0)
+    }
+}
+
+impl Default for SimulatedPs4262 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl State {
+    fn index(&self, channel: PS4000_CHANNEL) -> Option<usize> {
+        let idx = channel as usize;
+        (idx < self.variant.channel_count()).then_some(idx)
+    }
+
+    // Returns the maximum frequency and output voltage of the signal generator.
+    fn sig_gen_limits(&self, function: &'static str) -> Result<(f32, u64)> {
+        match (
+            self.variant.sig_gen_max_frequency(),
+            self.variant.sig_gen_max_voltage_uv(),
+        ) {
+            (Some(frequency), Some(voltage_uv)) => Ok((frequency, voltage_uv as u64)),
+            _ => Err(Error::driver(
+                function,
+                PicoStatus::NOT_SUPPORTED_BY_THIS_DEVICE,
+            )),
+        }
+    }
+
+    fn interval_ns(&self, timebase: u32) -> i32 {
+        (self.variant.interval(timebase) * 1e9) as i32
+    }
+
+    fn max_samples(&self) -> i32 {
+        MAX_SAMPLES
+            / self.segments as i32
+            / self.channels.iter().filter(|ch| ch.enabled).count().max(1) as i32
+    }
+
+    fn noise(&self, ch: usize, n: usize, k: u64) -> f64 {
+        fn splitmix64(mut x: u64) -> u64 {
+            x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            x ^ (x >> 31)
+        }
+        let key = self.seed
+            ^ self.captures.wrapping_shl(40)
+            ^ (ch as u64).wrapping_shl(36)
+            ^ (n as u64).wrapping_shl(2)
+            ^ k;
+        let u1 = (splitmix64(key) >> 11) as f64 / (1u64 << 53) as f64;
+        let u2 = (splitmix64(key ^ 0xffff_ffff) >> 11) as f64 / (1u64 << 53) as f64;
+        (-2.0 * (1.0 - u1).ln()).sqrt() * (2.0 * PI * u2).cos()
+    }
+
+    // Returns the ADC count and whether the input exceeded the range.
+    fn sample(&self, ch: usize, n: usize, dt: f64) -> (i16, bool) {
+        let mut k = 0;
+        let mv = self.signals[ch].value_mv(self.clock + n as f64 * dt, || {
+            k += 1;
+            self.noise(ch, n, k)
+        }) / self.probes[ch].value();
+        let max = self.variant.max_value() as f64;
+        let raw = mv / self.channels[ch].range_mv as f64 * max;
+        (raw.clamp(-max, max) as i16, raw.abs() > max)
+    }
+
+    // Whether `ch` is past its threshold, or inside its window.
+    #[allow(non_upper_case_globals)]
+    fn active(&self, ch: usize, n: usize, dt: f64) -> bool {
+        self.trigger.properties[ch].is_some_and(|trigger| {
+            let cur = self.sample(ch, n, dt).0;
+            match trigger.mode {
+                enThresholdMode_LEVEL => cur > trigger.upper,
+                _ => trigger.lower < cur && cur < trigger.upper,
+            }
+        })
+    }
+
+    fn pulse_level(&self, pulse_width: &PulseWidth, n: usize, dt: f64) -> bool {
+        let channels = std::array::from_fn(|ch| self.active(ch, n, dt));
+        let level = pulse_width
+            .conditions
+            .iter()
+            .any(|c| c.holds(channels, false));
+        level == (pulse_width.direction != enThresholdDirection_FALLING)
+    }
+
+    fn scan(&self, n: usize, dt: f64) -> Scan {
+        let mut scan = Scan::default();
+        for (ch, detector) in scan.detectors.iter_mut().enumerate() {
+            if let Some(trigger) = &self.trigger.properties[ch] {
+                *detector = Detector::new(trigger, self.sample(ch, n, dt).0);
+            }
+        }
+        if let Some(pulse_width) = self.trigger.pulse_width.as_ref() {
+            scan.pulse.level = self.pulse_level(pulse_width, n, dt);
+        }
+        scan
+    }
+
+    // Advances the scan to sample `n` and returns whether any condition holds there.
+    #[allow(non_upper_case_globals)]
+    fn triggered(&self, scan: &mut Scan, n: usize, dt: f64) -> bool {
+        let hits = std::array::from_fn(|ch| match &self.trigger.properties[ch] {
+            Some(trigger) if self.trigger.uses(ch) => scan.detectors[ch].step(
+                trigger,
+                self.trigger.directions[ch],
+                self.sample(ch, n, dt).0,
+            ),
+            _ => false,
+        });
+        let pulse_width = match self.trigger.pulse_width.as_ref() {
+            Some(pulse_width) if self.trigger.uses_pulse_width() => {
+                let level = self.pulse_level(pulse_width, n, dt);
+                scan.pulse
+                    .step(level, n, hits)
+                    .is_some_and(|width| pulse_width.qualifies(width))
+            }
+            _ => false,
+        };
+        self.trigger.conditions.iter().any(|c| {
+            let hits = match c.pulse_width_qualifier {
+                enTriggerState_CONDITION_TRUE => scan.pulse.hits,
+                _ => hits,
+            };
+            c.holds(hits, pulse_width)
+        })
+    }
+
+    fn start_search(&self, pre: usize, dt: f64) -> Search {
+        let horizon = match self.trigger.auto_trigger_ms {
+            auto_trigger_ms if auto_trigger_ms > 0 => {
+                pre + (auto_trigger_ms as f64 * 1e-3 / dt) as usize
+            }
+            _ => pre + MAX_TRIGGER_SEARCH,
+        };
+        Search {
+            scan: self.scan(pre.saturating_sub(1), dt),
+            next: pre.max(1),
+            horizon,
+        }
+    }
+
+    // Searches up to `budget` more samples and returns the trigger point once it is known,
+    // which is `None` if the trigger never fires.
+    fn search_trigger(&self, search: &mut Search, dt: f64, budget: usize) -> Option<Option<usize>> {
+        let end = search.horizon.min(search.next + budget);
+        while search.next < end {
+            let n = search.next;
+            search.next += 1;
+            if self.triggered(&mut search.scan, n, dt) {
+                return Some(Some(n));
+            }
+        }
+        if search.next < search.horizon {
+            return None;
+        }
+        Some((self.trigger.auto_trigger_ms > 0).then_some(search.horizon))
+    }
+
+    fn capture_segment(&mut self, start: usize, pre: usize, post: usize, dt: f64) -> Segment {
+        let mut overflow = 0i16;
+        let samples = (0..MAX_CHANNELS)
+            .map(|ch| {
+                if !self.channels[ch].enabled {
+                    return None;
+                }
+                Some(
+                    (start..start + pre + post)
+                        .map(|n| {
+                            let (v, over) = self.sample(ch, n, dt);
+                            if over {
+                                overflow |= 1 << ch;
+                            }
+                            v
+                        })
+                        .collect(),
+                )
+            })
+            .collect();
+
+        self.clock += (start + pre + post) as f64 * dt;
+        self.captures += 1;
+        Segment { samples, overflow }
+    }
+
+    fn run_block(&mut self, pre: usize, post: usize, timebase: u32) -> Result<(), PicoStatus> {
+        if self.trigger.enabled()
+            && (0..MAX_CHANNELS).any(|ch| self.trigger.uses(ch) && !self.channels[ch].enabled)
+        {
+            return Err(PicoStatus::INVALID_TRIGGER_CHANNEL);
+        }
+
+        self.run += 1;
+        self.capture = Some(Capture::Running(Run {
+            pre,
+            post,
+            dt: self.variant.interval(timebase),
+            segments: Vec::with_capacity(self.no_of_captures as _),
+            search: None,
+        }));
+        self.advance(TRIGGER_SEARCH_STEP);
+        Ok(())
+    }
+
+    // Continues the block capture in progress, searching up to `budget` samples for triggers.
+    fn advance(&mut self, mut budget: usize) {
+        let Some(Capture::Running(mut run)) = self.capture.take() else {
+            return;
+        };
+        while run.segments.len() < self.no_of_captures as usize {
+            let start = if self.trigger.enabled() {
+                let search = run
+                    .search
+                    .get_or_insert_with(|| self.start_search(run.pre, run.dt));
+                let searched = search.next;
+                let found = self.search_trigger(search, run.dt, budget);
+                budget = budget.saturating_sub(search.next - searched);
+                match found {
+                    Some(Some(n)) => n + self.trigger.delay as usize - run.pre,
+                    Some(None) => {
+                        self.capture = Some(Capture::Waiting);
+                        return;
+                    }
+                    None => {
+                        self.capture = Some(Capture::Running(run));
+                        return;
+                    }
+                }
+            } else {
+                0
+            };
+            run.search = None;
+            let segment = self.capture_segment(start, run.pre, run.post, run.dt);
+            run.segments.push(segment);
+        }
+        self.capture = Some(Capture::Done(run.segments));
+    }
+
+    // Writes the samples produced since the last call into the data buffers.
+    fn stream_latest(&mut self) -> Result<Option<StreamingReady>, PicoStatus> {
+        let Some(stream) = self.stream.as_ref() else {
+            return Err(PicoStatus::INVALID_CALL);
+        };
+        if stream.stopped {
+            return Ok(None);
+        }
+
+        let capacity = (0..MAX_CHANNELS)
+            .filter(|&ch| self.channels[ch].enabled)
+            .map(|ch| self.buffers[ch].as_ref().map_or(0, |b| b.len))
+            .min()
+            .unwrap_or(0);
+        let elapsed = (stream.started.elapsed().as_secs_f64() / stream.dt) as usize;
+        let start = stream.produced;
+        let mut end = elapsed.min(start + capacity);
+        if end <= start {
+            return Ok(None);
+        }
+
+        let mut triggered_at = stream.triggered_at;
+        let mut scan = stream.scan;
+        if !self.trigger.enabled() {
+            triggered_at = Some(stream.pre);
+        } else if triggered_at.is_none() {
+            for n in start..end {
+                if self.triggered(&mut scan, n, stream.dt) && n >= stream.pre.max(1) {
+                    triggered_at = Some(n);
+                    break;
+                }
+            }
+        }
+        let auto_stop = match triggered_at {
+            Some(t) if stream.auto_stop && end >= t + stream.post => {
+                end = t + stream.post;
+                true
+            }
+            _ => false,
+        };
+
+        let mut overflow = 0i16;
+        for ch in (0..MAX_CHANNELS).filter(|&ch| self.channels[ch].enabled) {
+            let Some(buffer) = self.buffers[ch].as_ref() else {
+                continue;
+            };
+            for (i, n) in (start..end).enumerate() {
+                let (v, over) = self.sample(ch, n, stream.dt);
+                if over {
+                    overflow |= 1 << ch;
+                }
+                unsafe {
+                    *buffer.max.add(i) = v;
+                }
+            }
+        }
+
+        let triggered = self.trigger.enabled()
+            && stream.triggered_at.is_none()
+            && triggered_at.is_some_and(|t| t < end);
+        let stream = self.stream.as_mut().unwrap();
+        stream.produced = end;
+        stream.triggered_at = triggered_at;
+        stream.scan = scan;
+        stream.stopped = auto_stop;
+
+        Ok(Some(StreamingReady {
+            no_of_samples: (end - start) as i32,
+            start_index: 0,
+            overflow,
+            trigger_at: triggered_at.map_or(0, |t| t.saturating_sub(start)) as u32,
+            triggered,
+            auto_stop,
+        }))
+    }
+}
+
+impl Ps4000Driver for SimulatedPs4262 {
+    fn open_unit(&self) -> Result<i16> {
+        let mut state = self.state.lock().unwrap();
+        if state.open {
+            return Err(Error::driver(
+                "ps4000OpenUnit",
+                PicoStatus::MAX_UNITS_OPENED,
+            ));
+        }
+        state.open = true;
+        Ok(HANDLE)
+    }
+
+    fn close_unit(&self, handle: i16) -> Result<()> {
+        let mut state = self.state(handle, "ps4000CloseUnit")?;
+        state.open = false;
+        state.capture = None;
+        state.stream = None;
+        state.buffers = Default::default();
+        state.bulk_buffers.clear();
+        Ok(())
+    }
+
+    fn enumerate_units(&self) -> Result<Vec<String>> {
+        let state = self.state.lock().unwrap();
+        Ok(match state.open {
+            true => Vec::new(),
+            false => vec![state.serial.clone()],
+        })
+    }
+
+    fn open_unit_ex(&self, serial: &str) -> Result<i16> {
+        if self.state.lock().unwrap().serial != serial {
+            return Err(Error::driver("ps4000OpenUnitEx", PicoStatus::NOT_FOUND));
+        }
+        self.open_unit()
+            .map_err(|e| Error::driver("ps4000OpenUnitEx", e.status().unwrap()))
+    }
+
+    fn open_unit_async(&self) -> Result<()> {
+        let mut state = self.state.lock().unwrap();
+        if state.open || state.opening.is_some() {
+            return Err(Error::driver("ps4000OpenUnitAsync", PicoStatus::NOT_FOUND));
+        }
+        state.opening = Some(0);
+        Ok(())
+    }
+
+    fn open_unit_progress(&self) -> Result<(i16, i16, bool)> {
+        let mut state = self.state.lock().unwrap();
+        let Some(progress) = state.opening else {
+            return Err(Error::driver(
+                "ps4000OpenUnitProgress",
+                PicoStatus::OPERATION_FAILED,
+            ));
+        };
+        let progress = (progress + OPEN_PROGRESS_STEP).min(100);
+        if progress < 100 {
+            state.opening = Some(progress);
+            return Ok((0, progress, false));
+        }
+        state.opening = None;
+        state.open = true;
+        Ok((HANDLE, progress, true))
+    }
+
+    fn get_unit_info(&self, handle: i16, info: PicoInfo) -> Result<String> {
+        let state = self.state(handle, "ps4000GetUnitInfo")?;
+        Ok(match info {
+            PicoInfo::DRIVER_VERSION => format!("{} (simulated)", env!("CARGO_PKG_VERSION")),
+            PicoInfo::USB_VERSION => "2.0".to_string(),
+            PicoInfo::HARDWARE_VERSION => "1".to_string(),
+            PicoInfo::VARIANT_INFO => state.variant.to_string().replace("PicoScope ", ""),
+            PicoInfo::BATCH_AND_SERIAL => state.serial.clone(),
+            PicoInfo::CAL_DATE => "01Jan26".to_string(),
+            PicoInfo::KERNEL_VERSION => "1.0".to_string(),
+            PicoInfo::DIGITAL_HARDWARE_VERSION | PicoInfo::ANALOGUE_HARDWARE_VERSION => {
+                "1".to_string()
+            }
+            PicoInfo::FIRMWARE_VERSION_1 | PicoInfo::FIRMWARE_VERSION_2 => "1.0.0.0".to_string(),
+            _ => return Err(Error::driver("ps4000GetUnitInfo", PicoStatus::INVALID_INFO)),
+        })
+    }
+
+    fn set_channel(
+        &self,
+        handle: i16,
+        channel: PS4000_CHANNEL,
+        enabled: bool,
+        _coupling: PicoCoupling,
+        range: PS4000_RANGE,
+    ) -> Result<()> {
+        let mut state = self.state(handle, "ps4000SetChannel")?;
+        let idx = state.index(channel).ok_or(Error::driver(
+            "ps4000SetChannel",
+            PicoStatus::INVALID_CHANNEL,
+        ))?;
+        let range_mv = range_mv(range).ok_or(Error::driver(
+            "ps4000SetChannel",
+            PicoStatus::INVALID_VOLTAGE_RANGE,
+        ))?;
+        if !state.variant.supports_range(range) {
+            return Err(Error::driver(
+                "ps4000SetChannel",
+                PicoStatus::INVALID_VOLTAGE_RANGE,
+            ));
+        }
+        state.channels[idx] = ChannelState { enabled, range_mv };
+        Ok(())
+    }
+
+    fn set_simple_trigger(
+        &self,
+        handle: i16,
+        enable: bool,
+        source: PS4000_CHANNEL,
+        threshold: i16,
+        direction: THRESHOLD_DIRECTION,
+        delay: u32,
+        auto_trigger_ms: i16,
+    ) -> Result<()> {
+        let mut state = self.state(handle, "ps4000SetSimpleTrigger")?;
+        let source = state.index(source).ok_or(Error::driver(
+            "ps4000SetSimpleTrigger",
+            PicoStatus::INVALID_TRIGGER_CHANNEL,
+        ))?;
+        let mut trigger = TriggerSetup::new();
+        if enable {
+            trigger.properties[source] = Some(ChannelTrigger {
+                mode: enThresholdMode_LEVEL,
+                upper: threshold,
+                upper_hysteresis: 0,
+                lower: threshold,
+                lower_hysteresis: 0,
+            });
+            trigger.directions[source] = direction;
+            let mut channels = [enTriggerState_CONDITION_DONT_CARE; MAX_CHANNELS];
+            channels[source] = enTriggerState_CONDITION_TRUE;
+            trigger.conditions.push(Condition {
+                channels,
+                external: enTriggerState_CONDITION_DONT_CARE,
+                aux: enTriggerState_CONDITION_DONT_CARE,
+                pulse_width_qualifier: enTriggerState_CONDITION_DONT_CARE,
+            });
+            trigger.delay = delay;
+            trigger.auto_trigger_ms = auto_trigger_ms as i32;
+        }
+        state.trigger = trigger;
+        Ok(())
+    }
+
+    fn set_trigger_channel_conditions(
+        &self,
+        handle: i16,
+        conditions: &[TRIGGER_CONDITIONS],
+    ) -> Result<()> {
+        let mut state = self.state(handle, "ps4000SetTriggerChannelConditions")?;
+        let conditions = conditions
+            .iter()
+            .map(|c| {
+                let condition = Condition {
+                    channels: [c.channelA, c.channelB, c.channelC, c.channelD],
+                    external: c.external,
+                    aux: c.aux,
+                    pulse_width_qualifier: c.pulseWidthQualifier,
+                };
+                if !condition.is_available(state.variant) {
+                    return Err(Error::driver(
+                        "ps4000SetTriggerChannelConditions",
+                        PicoStatus::INVALID_TRIGGER_CHANNEL,
+                    ));
+                }
+                Ok(condition)
+            })
+            .collect::<Result<_>>()?;
+        state.trigger.conditions = conditions;
+        Ok(())
+    }
+
+    fn set_trigger_channel_directions(
+        &self,
+        handle: i16,
+        channel_a: THRESHOLD_DIRECTION,
+        channel_b: THRESHOLD_DIRECTION,
+        channel_c: THRESHOLD_DIRECTION,
+        channel_d: THRESHOLD_DIRECTION,
+        _ext: THRESHOLD_DIRECTION,
+        _aux: THRESHOLD_DIRECTION,
+    ) -> Result<()> {
+        let mut state = self.state(handle, "ps4000SetTriggerChannelDirections")?;
+        state.trigger.directions = [channel_a, channel_b, channel_c, channel_d];
+        Ok(())
+    }
+
+    fn set_trigger_channel_properties(
+        &self,
+        handle: i16,
+        properties: &[TRIGGER_CHANNEL_PROPERTIES],
+        _aux_output_enable: bool,
+        auto_trigger_ms: i32,
+    ) -> Result<()> {
+        let mut state = self.state(handle, "ps4000SetTriggerChannelProperties")?;
+        let mut channels = [None; MAX_CHANNELS];
+        for p in properties {
+            let idx = state.index(p.channel).ok_or(Error::driver(
+                "ps4000SetTriggerChannelProperties",
+                PicoStatus::INVALID_TRIGGER_CHANNEL,
+            ))?;
+            channels[idx] = Some(ChannelTrigger {
+                mode: p.thresholdMode,
+                upper: p.thresholdUpper,
+                upper_hysteresis: p.thresholdUpperHysteresis,
+                lower: p.thresholdLower,
+                lower_hysteresis: p.thresholdLowerHysteresis,
+            });
+        }
+        state.trigger.properties = channels;
+        state.trigger.auto_trigger_ms = auto_trigger_ms;
+        Ok(())
+    }
+
+    fn set_trigger_delay(&self, handle: i16, delay: u32) -> Result<()> {
+        let mut state = self.state(handle, "ps4000SetTriggerDelay")?;
+        state.trigger.delay = delay;
+        Ok(())
+    }
+
+    fn set_pulse_width_qualifier(
+        &self,
+        handle: i16,
+        conditions: &[PWQ_CONDITIONS],
+        direction: THRESHOLD_DIRECTION,
+        lower: u32,
+        upper: u32,
+        ty: PULSE_WIDTH_TYPE,
+    ) -> Result<()> {
+        let mut state = self.state(handle, "ps4000SetPulseWidthQualifier")?;
+        let conditions = conditions
+            .iter()
+            .map(|c| {
+                let condition = Condition {
+                    channels: [c.channelA, c.channelB, c.channelC, c.channelD],
+                    external: c.external,
+                    aux: c.aux,
+                    pulse_width_qualifier: enTriggerState_CONDITION_DONT_CARE,
+                };
+                if !condition.is_available(state.variant) {
+                    return Err(Error::driver(
+                        "ps4000SetPulseWidthQualifier",
+                        PicoStatus::INVALID_TRIGGER_CHANNEL,
+                    ));
+                }
+                Ok(condition)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        state.trigger.pulse_width = (!conditions.is_empty() && ty != enPulseWidthType_PW_TYPE_NONE)
+            .then_some(PulseWidth {
+                conditions,
+                direction,
+                lower,
+                upper,
+                ty,
+            });
+        Ok(())
+    }
+
+    fn set_sig_gen_built_in(
+        &self,
+        handle: i16,
+        offset_voltage: i32,
+        pk_to_pk: u32,
+        wave_type: WAVE_TYPE,
+        start_frequency: f32,
+        stop_frequency: f32,
+        _increment: f32,
+        _dwell_time: f32,
+        sweep_type: SWEEP_TYPE,
+        _operation: PS4000_OPERATION_TYPES,
+        _shots: u32,
+        _sweeps: u32,
+        _trigger_type: SIGGEN_TRIG_TYPE,
+        _trigger_source: SIGGEN_TRIG_SOURCE,
+        _ext_in_threshold: i16,
+    ) -> Result<()> {
+        let (max_frequency, max_voltage_uv) = self
+            .state(handle, "ps4000SetSigGenBuiltIn")?
+            .sig_gen_limits("ps4000SetSigGenBuiltIn")?;
+        if wave_type >= enWaveType_MAX_WAVE_TYPES {
+            return Err(Error::driver(
+                "ps4000SetSigGenBuiltIn",
+                PicoStatus::SIG_GEN_WAVETYPE_NOT_SUPPORTED,
+            ));
+        }
+        if sweep_type >= enSweepType_MAX_SWEEP_TYPES {
+            return Err(Error::driver(
+                "ps4000SetSigGenBuiltIn",
+                PicoStatus::SIGGEN_SWEEPTYPE_INVALID,
+            ));
+        }
+        if offset_voltage.unsigned_abs() as u64 + pk_to_pk as u64 / 2 > max_voltage_uv {
+            return Err(Error::driver(
+                "ps4000SetSigGenBuiltIn",
+                PicoStatus::SIGGEN_OUTPUT_OVER_VOLTAGE,
+            ));
+        }
+        if [start_frequency, stop_frequency]
+            .iter()
+            .any(|f| !(0.0..=max_frequency).contains(f))
+        {
+            return Err(Error::driver(
+                "ps4000SetSigGenBuiltIn",
+                PicoStatus::SIGGEN_FREQUENCY_OUT_OF_RANGE,
+            ));
+        }
+        Ok(())
+    }
+
+    fn sig_gen_software_control(&self, handle: i16, _state: bool) -> Result<()> {
+        self.state(handle, "ps4000SigGenSoftwareControl")?
+            .sig_gen_limits("ps4000SigGenSoftwareControl")
+            .map(|_| ())
+    }
+
+    fn sig_gen_off(&self, handle: i16) -> Result<()> {
+        self.state(handle, "ps4000SigGenOff")?
+            .sig_gen_limits("ps4000SigGenOff")
+            .map(|_| ())
+    }
+
+    fn set_sig_gen_arbitrary(
+        &self,
+        handle: i16,
+        offset_voltage: i32,
+        pk_to_pk: u32,
+        start_delta_phase: u32,
+        stop_delta_phase: u32,
+        _delta_phase_increment: u32,
+        _dwell_count: u32,
+        waveform: &[i16],
+        sweep_type: SWEEP_TYPE,
+        _operation: PS4000_OPERATION_TYPES,
+        index_mode: INDEX_MODE,
+        _shots: u32,
+        _sweeps: u32,
+        _trigger_type: SIGGEN_TRIG_TYPE,
+        _trigger_source: SIGGEN_TRIG_SOURCE,
+        _ext_in_threshold: i16,
+    ) -> Result<()> {
+        let (_, max_voltage_uv) = self
+            .state(handle, "ps4000SetSigGenArbitrary")?
+            .sig_gen_limits("ps4000SetSigGenArbitrary")?;
+        if !(AWG_MIN_SIZE..=AWG_MAX_SIZE).contains(&(waveform.len() as u32)) {
+            return Err(Error::driver(
+                "ps4000SetSigGenArbitrary",
+                PicoStatus::SIGGEN_TOO_MANY_SAMPLES,
+            ));
+        }
+        if index_mode != enIndexMode_SINGLE || sweep_type >= enSweepType_MAX_SWEEP_TYPES {
+            return Err(Error::driver(
+                "ps4000SetSigGenArbitrary",
+                PicoStatus::INVALID_PARAMETER,
+            ));
+        }
+        if offset_voltage.unsigned_abs() as u64 + pk_to_pk as u64 / 2 > max_voltage_uv {
+            return Err(Error::driver(
+                "ps4000SetSigGenArbitrary",
+                PicoStatus::SIGGEN_OUTPUT_OVER_VOLTAGE,
+            ));
+        }
+        if start_delta_phase == 0 || stop_delta_phase == 0 {
+            return Err(Error::driver(
+                "ps4000SetSigGenArbitrary",
+                PicoStatus::SIGGEN_FREQUENCY_OUT_OF_RANGE,
+            ));
+        }
+        Ok(())
+    }
+
+    fn sig_gen_frequency_to_phase(
+        &self,
+        handle: i16,
+        frequency: f64,
+        index_mode: INDEX_MODE,
+        buffer_length: u32,
+    ) -> Result<u32> {
+        self.state(handle, "ps4000SigGenFrequencyToPhase")?
+            .sig_gen_limits("ps4000SigGenFrequencyToPhase")?;
+        if index_mode != enIndexMode_SINGLE
+            || !(AWG_MIN_SIZE..=AWG_MAX_SIZE).contains(&buffer_length)
+        {
+            return Err(Error::driver(
+                "ps4000SigGenFrequencyToPhase",
+                PicoStatus::INVALID_PARAMETER,
+            ));
+        }
+        let phase = frequency * buffer_length as f64 / AWG_MAX_SIZE as f64 / AWG_DAC_FREQUENCY
+            * (1u64 << 32) as f64;
+        if !(1.0..=u32::MAX as f64).contains(&phase.round()) {
+            return Err(Error::driver(
+                "ps4000SigGenFrequencyToPhase",
+                PicoStatus::SIGGEN_FREQUENCY_OUT_OF_RANGE,
+            ));
+        }
+        Ok(phase.round() as u32)
+    }
+
+    fn sig_gen_arbitrary_min_max_values(&self, handle: i16) -> Result<(i16, i16, u32, u32)> {
+        self.state(handle, "ps4000SigGenArbitraryMinMaxValues")?
+            .sig_gen_limits("ps4000SigGenArbitraryMinMaxValues")?;
+        Ok((i16::MIN, i16::MAX, AWG_MIN_SIZE, AWG_MAX_SIZE))
+    }
+
+    unsafe fn set_data_buffers(
+        &self,
+        handle: i16,
+        channel: PS4000_CHANNEL,
+        buffer_max: *mut i16,
+        buffer_min: *mut i16,
+        buffer_len: i32,
+    ) -> Result<()> {
+        let mut state = self.state(handle, "ps4000SetDataBuffers")?;
+        let idx = state.index(channel).ok_or(Error::driver(
+            "ps4000SetDataBuffers",
+            PicoStatus::INVALID_CHANNEL,
+        ))?;
+        state.buffers[idx] = (!buffer_max.is_null()).then_some(DataBuffer {
+            max: buffer_max,
+            min: buffer_min,
+            len: buffer_len.max(0) as usize,
+        });
+        Ok(())
+    }
+
+    fn get_timebase(
+        &self,
+        handle: i16,
+        timebase: u32,
+        no_of_samples: i32,
+        _oversample: i16,
+        _segment_index: u16,
+    ) -> Result<(i32, i32)> {
+        let state = self.state(handle, "ps4000GetTimebase")?;
+        if timebase > state.variant.max_timebase() {
+            return Err(Error::driver(
+                "ps4000GetTimebase",
+                PicoStatus::INVALID_TIMEBASE,
+            ));
+        }
+        let max_samples = state.max_samples();
+        if no_of_samples > max_samples {
+            return Err(Error::driver(
+                "ps4000GetTimebase",
+                PicoStatus::TOO_MANY_SAMPLES,
+            ));
+        }
+        Ok((state.interval_ns(timebase), max_samples))
+    }
+
+    fn run_block(
+        &self,
+        handle: i16,
+        no_of_pre_trigger_samples: i32,
+        no_of_post_trigger_samples: i32,
+        timebase: u32,
+        _oversample: i16,
+        _segment_index: u16,
+    ) -> Result<i32> {
+        let mut state = self.state(handle, "ps4000RunBlock")?;
+        if no_of_pre_trigger_samples < 0
+            || no_of_post_trigger_samples < 0
+            || no_of_pre_trigger_samples + no_of_post_trigger_samples == 0
+        {
+            return Err(Error::driver(
+                "ps4000RunBlock",
+                PicoStatus::INVALID_PARAMETER,
+            ));
+        }
+        if no_of_pre_trigger_samples + no_of_post_trigger_samples > state.max_samples() {
+            return Err(Error::driver(
+                "ps4000RunBlock",
+                PicoStatus::TOO_MANY_SAMPLES,
+            ));
+        }
+        if timebase > state.variant.max_timebase() {
+            return Err(Error::driver(
+                "ps4000RunBlock",
+                PicoStatus::INVALID_TIMEBASE,
+            ));
+        }
+        state
+            .run_block(
+                no_of_pre_trigger_samples as usize,
+                no_of_post_trigger_samples as usize,
+                timebase,
+            )
+            .map_err(|status| Error::driver("ps4000RunBlock", status))?;
+        Ok(0)
+    }
+
+    fn run_block_with_callback(
+        &self,
+        handle: i16,
+        no_of_pre_trigger_samples: i32,
+        no_of_post_trigger_samples: i32,
+        timebase: u32,
+        oversample: i16,
+        segment_index: u16,
+        ready: BlockReady,
+    ) -> Result<i32> {
+        let time_indisposed_ms = self.run_block(
+            handle,
+            no_of_pre_trigger_samples,
+            no_of_post_trigger_samples,
+            timebase,
+            oversample,
+            segment_index,
+        )?;
+        // The trigger search continues here, and `ready` is dropped if the capture is stopped or replaced.
+        let state = self.state.clone();
+        let run = state.lock().unwrap().run;
+        std::thread::spawn(move || loop {
+            let mut state = state.lock().unwrap();
+            if !state.open || state.run != run {
+                return;
+            }
+            match state.capture {
+                Some(Capture::Running(_)) => state.advance(TRIGGER_SEARCH_STEP),
+                Some(Capture::Done(_)) => {
+                    drop(state);
+                    ready(PicoStatus::OK);
+                    return;
+                }
+                _ => return,
+            }
+            // let the caller stop the capture between steps
+            drop(state);
+            std::thread::yield_now();
+        });
+        Ok(time_indisposed_ms)
+    }
+
+    fn is_ready(&self, handle: i16) -> Result<bool> {
+        let mut state = self.state(handle, "ps4000IsReady")?;
+        state.advance(TRIGGER_SEARCH_STEP);
+        Ok(matches!(state.capture, Some(Capture::Done(_))))
+    }
+
+    fn get_values(
+        &self,
+        handle: i16,
+        start_index: u32,
+        no_of_samples: u32,
+        down_sample_ratio: u32,
+        down_sample_ratio_mode: RATIO_MODE,
+        segment_index: u16,
+    ) -> Result<(u32, i16)> {
+        let state = self.state(handle, "ps4000GetValues")?;
+        check_ratio_mode(down_sample_ratio_mode)
+            .map_err(|status| Error::driver("ps4000GetValues", status))?;
+        if down_sample_ratio == 0 {
+            return Err(Error::driver(
+                "ps4000GetValues",
+                PicoStatus::INVALID_SAMPLERATIO,
+            ));
+        }
+        let segment = match &state.capture {
+            Some(Capture::Done(segments)) => segments.get(segment_index as usize).ok_or(
+                Error::driver("ps4000GetValues", PicoStatus::SEGMENT_OUT_OF_RANGE),
+            )?,
+            _ => {
+                return Err(Error::driver(
+                    "ps4000GetValues",
+                    PicoStatus::NO_SAMPLES_AVAILABLE,
+                ))
+            }
+        };
+
+        let mut retrieved = 0;
+        for (ch, buffer) in state.buffers.iter().enumerate() {
+            let Some(buffer) = buffer else {
+                continue;
+            };
+            if let Some(n) = segment
+                .copy_into(
+                    ch,
+                    start_index as _,
+                    no_of_samples as _,
+                    down_sample_ratio as _,
+                    down_sample_ratio_mode,
+                    buffer,
+                )
+                .map_err(|status| Error::driver("ps4000GetValues", status))?
+            {
+                retrieved = n;
+            }
+        }
+
+        Ok((retrieved as u32, segment.overflow))
+    }
+
+    fn get_max_down_sample_ratio(
+        &self,
+        handle: i16,
+        no_of_unaggregated_samples: u32,
+        down_sample_ratio_mode: RATIO_MODE,
+        segment_index: u16,
+    ) -> Result<u32> {
+        let state = self.state(handle, "ps4000GetMaxDownSampleRatio")?;
+        check_ratio_mode(down_sample_ratio_mode)
+            .map_err(|status| Error::driver("ps4000GetMaxDownSampleRatio", status))?;
+        let captured = match &state.capture {
+            Some(Capture::Done(segments)) => segments
+                .get(segment_index as usize)
+                .ok_or(Error::driver(
+                    "ps4000GetMaxDownSampleRatio",
+                    PicoStatus::SEGMENT_OUT_OF_RANGE,
+                ))?
+                .len(),
+            _ => {
+                return Err(Error::driver(
+                    "ps4000GetMaxDownSampleRatio",
+                    PicoStatus::NO_SAMPLES_AVAILABLE,
+                ))
+            }
+        };
+        if down_sample_ratio_mode == enRatioMode_RATIO_MODE_NONE {
+            return Ok(1);
+        }
+        Ok((no_of_unaggregated_samples as usize).min(captured).max(1) as u32)
+    }
+
+    fn stop(&self, handle: i16) -> Result<()> {
+        let mut state = self.state(handle, "ps4000Stop")?;
+        if matches!(state.capture, Some(Capture::Running(_) | Capture::Waiting)) {
+            state.capture = None;
+        }
+        if let Some(stream) = state.stream.take() {
+            state.clock += stream.produced as f64 * stream.dt;
+            state.captures += 1;
+        }
+        Ok(())
+    }
+
+    #[allow(non_upper_case_globals)]
+    fn run_streaming(
+        &self,
+        handle: i16,
+        sample_interval: u32,
+        time_units: PS4000_TIME_UNITS,
+        max_pre_trigger_samples: u32,
+        max_post_trigger_samples: u32,
+        auto_stop: bool,
+        down_sample_ratio: u32,
+        _overview_buffer_size: u32,
+    ) -> Result<u32> {
+        let mut state = self.state(handle, "ps4000RunStreaming")?;
+        let unit_ns = match time_units {
+            enPS4000TimeUnits_PS4000_FS => 1e-6,
+            enPS4000TimeUnits_PS4000_PS => 1e-3,
+            enPS4000TimeUnits_PS4000_NS => 1.0,
+            enPS4000TimeUnits_PS4000_US => 1e3,
+            enPS4000TimeUnits_PS4000_MS => 1e6,
+            enPS4000TimeUnits_PS4000_S => 1e9,
+            _ => {
+                return Err(Error::driver(
+                    "ps4000RunStreaming",
+                    PicoStatus::INVALID_PARAMETER,
+                ))
+            }
+        };
+        if down_sample_ratio != 1 {
+            return Err(Error::driver(
+                "ps4000RunStreaming",
+                PicoStatus::INVALID_SAMPLERATIO,
+            ));
+        }
+        let timebase = state
+            .variant
+            .timebase_for_interval(sample_interval as f64 * unit_ns * 1e-9);
+        if timebase > state.variant.max_timebase() {
+            return Err(Error::driver(
+                "ps4000RunStreaming",
+                PicoStatus::INVALID_SAMPLE_INTERVAL,
+            ));
+        }
+        let interval_ns = state.variant.interval(timebase) * 1e9;
+        let scan = state.scan(0, interval_ns * 1e-9);
+        state.capture = None;
+        state.stream = Some(Streaming {
+            dt: interval_ns * 1e-9,
+            started: Instant::now(),
+            produced: 0,
+            pre: max_pre_trigger_samples as usize,
+            post: max_post_trigger_samples as usize,
+            auto_stop,
+            triggered_at: None,
+            scan,
+            stopped: false,
+        });
+        Ok((interval_ns / unit_ns).round() as u32)
+    }
+
+    fn get_streaming_latest_values(
+        &self,
+        handle: i16,
+        callback: &mut dyn FnMut(StreamingReady),
+    ) -> Result<()> {
+        let ready = self
+            .state(handle, "ps4000GetStreamingLatestValues")?
+            .stream_latest()
+            .map_err(|status| Error::driver("ps4000GetStreamingLatestValues", status))?;
+        if let Some(ready) = ready {
+            callback(ready);
+        }
+        Ok(())
+    }
+
+    fn memory_segments(&self, handle: i16, no_of_segments: u16) -> Result<i32> {
+        let mut state = self.state(handle, "ps4000MemorySegments")?;
+        if no_of_segments == 0 {
+            return Err(Error::driver(
+                "ps4000MemorySegments",
+                PicoStatus::TOO_MANY_SEGMENTS,
+            ));
+        }
+        state.segments = no_of_segments;
+        state.no_of_captures = state.no_of_captures.min(no_of_segments);
+        state.capture = None;
+        Ok(MAX_SAMPLES / no_of_segments as i32)
+    }
+
+    fn set_no_of_captures(&self, handle: i16, no_of_captures: u16) -> Result<()> {
+        let mut state = self.state(handle, "ps4000SetNoOfCaptures")?;
+        if no_of_captures == 0 || no_of_captures > state.segments {
+            return Err(Error::driver(
+                "ps4000SetNoOfCaptures",
+                PicoStatus::NOT_ENOUGH_SEGMENTS,
+            ));
+        }
+        state.no_of_captures = no_of_captures;
+        Ok(())
+    }
+
+    unsafe fn set_data_buffer_bulk(
+        &self,
+        handle: i16,
+        channel: PS4000_CHANNEL,
+        buffer: *mut i16,
+        buffer_len: i32,
+        waveform: u16,
+    ) -> Result<()> {
+        let mut state = self.state(handle, "ps4000SetDataBufferBulk")?;
+        let idx = state.index(channel).ok_or(Error::driver(
+            "ps4000SetDataBufferBulk",
+            PicoStatus::INVALID_CHANNEL,
+        ))?;
+        if buffer.is_null() {
+            state.bulk_buffers.remove(&(idx, waveform));
+        } else {
+            state.bulk_buffers.insert(
+                (idx, waveform),
+                DataBuffer {
+                    max: buffer,
+                    min: std::ptr::null_mut(),
+                    len: buffer_len.max(0) as usize,
+                },
+            );
+        }
+        Ok(())
+    }
+
+    fn get_values_bulk(
+        &self,
+        handle: i16,
+        no_of_samples: u32,
+        from_segment_index: u16,
+        to_segment_index: u16,
+        overflow: &mut [i16],
+    ) -> Result<u32> {
+        let state = self.state(handle, "ps4000GetValuesBulk")?;
+        let Some(Capture::Done(segments)) = &state.capture else {
+            return Err(Error::driver(
+                "ps4000GetValuesBulk",
+                PicoStatus::NO_SAMPLES_AVAILABLE,
+            ));
+        };
+        if from_segment_index > to_segment_index
+            || to_segment_index as usize >= segments.len()
+            || overflow.len() <= (to_segment_index - from_segment_index) as usize
+        {
+            return Err(Error::driver(
+                "ps4000GetValuesBulk",
+                PicoStatus::SEGMENT_OUT_OF_RANGE,
+            ));
+        }
+
+        let mut retrieved = 0;
+        for (i, seg) in (from_segment_index..=to_segment_index).enumerate() {
+            let segment = &segments[seg as usize];
+            for ch in 0..MAX_CHANNELS {
+                let Some(buffer) = state.bulk_buffers.get(&(ch, seg)) else {
+                    continue;
+                };
+                if let Some(n) = segment
+                    .copy_into(
+                        ch,
+                        0,
+                        no_of_samples as _,
+                        1,
+                        enRatioMode_RATIO_MODE_NONE,
+                        buffer,
+                    )
+                    .map_err(|status| Error::driver("ps4000GetValuesBulk", status))?
+                {
+                    retrieved = n;
+                }
+            }
+            overflow[i] = segment.overflow;
+        }
+        Ok(retrieved as u32)
+    }
+
+    fn get_values_trigger_time_offset_bulk64(
+        &self,
+        handle: i16,
+        times: &mut [i64],
+        time_units: &mut [PS4000_TIME_UNITS],
+        from_segment_index: u16,
+        to_segment_index: u16,
+    ) -> Result<()> {
+        let state = self.state(handle, "ps4000GetValuesTriggerTimeOffsetBulk64")?;
+        let n = (to_segment_index as usize + 1).saturating_sub(from_segment_index as usize);
+        if !matches!(&state.capture, Some(Capture::Done(segments)) if segments.len() > to_segment_index as usize)
+            || times.len() < n
+            || time_units.len() < n
+        {
+            return Err(Error::driver(
+                "ps4000GetValuesTriggerTimeOffsetBulk64",
+                PicoStatus::SEGMENT_OUT_OF_RANGE,
+            ));
+        }
+        // The simulated trigger always fires exactly on a sample.
+        times[..n].fill(0);
+        time_units[..n].fill(enPS4000TimeUnits_PS4000_NS);
+        Ok(())
+    }
+}
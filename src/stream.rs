@@ -0,0 +1,263 @@
+/*
+ * File: stream.rs
+ * Project: src
+ * Created Date: 18/10/2026
+ * Author: Shun Suzuki
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: Shun Suzuki (suzuki@hapis.k.u-tokyo.ac.jp)
+ * -----
+ * Copyright (c) 2026 Shun Suzuki. All rights reserved.
+ *
+ */
+
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::Receiver,
+        Arc,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use pico_common::PicoStatus;
+use pico_sys_dynamic::ps4000::PS4000_CHANNEL;
+
+use crate::{
+    attenuation::Attenuation, driver::Ps4000Driver, ps4000::HandleGuard, trigger::Trigger,
+    variant::convert_adc_to_mv, Channel, Error, Result,
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StreamConfig {
+    pub(crate) sample_rate: u32,
+    pub(crate) buffer_size: u32,
+    pub(crate) auto_stop: Option<u32>,
+    pub(crate) trigger: Option<Trigger>,
+}
+
+impl StreamConfig {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            sample_rate,
+            buffer_size: 100_000,
+            auto_stop: None,
+            trigger: None,
+        }
+    }
+
+    /// Sets the size of the per-channel buffer the driver writes into between two polls.
+    pub fn with_buffer_size(mut self, buffer_size: u32) -> Self {
+        self.buffer_size = buffer_size;
+        self
+    }
+
+    /// Stops streaming after `no_of_post_trigger_samples` samples following the trigger,
+    /// or after that many samples in total if no trigger is set.
+    pub fn with_auto_stop(mut self, no_of_post_trigger_samples: u32) -> Self {
+        self.auto_stop = Some(no_of_post_trigger_samples);
+        self
+    }
+
+    /// Sets the trigger; its number of pre-trigger samples is used as the pre-trigger count of the stream.
+    pub fn with_trigger(mut self, trigger: Trigger) -> Self {
+        self.trigger = Some(trigger);
+        self
+    }
+}
+
+pub struct StreamChunk {
+    start: u64,
+    overflow: bool,
+    trigger_at: Option<usize>,
+    auto_stop: bool,
+    samples: HashMap<PS4000_CHANNEL, Vec<i16>>,
+    range_mv: HashMap<PS4000_CHANNEL, i32>,
+    attenuation: HashMap<PS4000_CHANNEL, Attenuation>,
+    max_value: u32,
+}
+
+impl StreamChunk {
+    /// Index of the first sample of this chunk in the stream.
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.values().next().map_or(0, |v| v.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn overflow(&self) -> bool {
+        self.overflow
+    }
+
+    /// Index of the trigger point within this chunk, if the trigger fired in it.
+    pub fn trigger_at(&self) -> Option<usize> {
+        self.trigger_at
+    }
+
+    /// Whether the stream stopped automatically after this chunk.
+    pub fn auto_stop(&self) -> bool {
+        self.auto_stop
+    }
+
+    pub fn get_adc(&self, channel: Channel) -> Vec<i16> {
+        self.samples
+            .get(&channel.into())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn get_mv(&self, channel: Channel) -> Vec<f64> {
+        let channel = channel.into();
+        let Some(samples) = self.samples.get(&channel) else {
+            return Vec::new();
+        };
+        let range_mv = self.range_mv[&channel];
+        let atten = self.attenuation[&channel];
+        samples
+            .iter()
+            .map(|&x| convert_adc_to_mv(x, self.max_value, atten, range_mv))
+            .collect()
+    }
+}
+
+/// A running streaming acquisition.
+///
+/// Chunks are yielded by iterating over the handle, unless it was started with a callback.
+/// Dropping the handle stops streaming.
+pub struct StreamHandle {
+    stop: Arc<AtomicBool>,
+    rx: Option<Receiver<StreamChunk>>,
+    worker: Option<JoinHandle<Result<()>>>,
+    time_interval: Duration,
+}
+
+impl StreamHandle {
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn spawn<D: Ps4000Driver + 'static>(
+        driver: Arc<D>,
+        handle: i16,
+        buffers: HashMap<PS4000_CHANNEL, Vec<i16>>,
+        range_mv: HashMap<PS4000_CHANNEL, i32>,
+        attenuation: HashMap<PS4000_CHANNEL, Attenuation>,
+        max_value: u32,
+        guard: HandleGuard,
+        time_interval: Duration,
+        rx: Option<Receiver<StreamChunk>>,
+        mut sink: Box<dyn FnMut(StreamChunk) + Send>,
+    ) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let worker = {
+            let stop = stop.clone();
+            std::thread::spawn(move || {
+                let mut start = 0u64;
+                let result = (|| {
+                    while !stop.load(Ordering::Acquire) {
+                        let mut chunks = Vec::new();
+                        let res = guard.with(|| {
+                            driver.get_streaming_latest_values(handle, &mut |ready| {
+                                let begin = ready.start_index as usize;
+                                let n = ready.no_of_samples.max(0) as usize;
+                                chunks.push(StreamChunk {
+                                    start,
+                                    overflow: ready.overflow != 0,
+                                    trigger_at: ready
+                                        .triggered
+                                        .then_some(ready.trigger_at as usize),
+                                    auto_stop: ready.auto_stop,
+                                    samples: buffers
+                                        .iter()
+                                        .map(|(&ch, buf)| {
+                                            let end = (begin + n).min(buf.len());
+                                            (ch, buf[begin.min(end)..end].to_vec())
+                                        })
+                                        .collect(),
+                                    range_mv: range_mv.clone(),
+                                    attenuation: attenuation.clone(),
+                                    max_value,
+                                });
+                                start += n as u64;
+                            })
+                        });
+                        match res {
+                            Err(e) if e.status() == Some(PicoStatus::BUSY) => {}
+                            res => res?,
+                        }
+
+                        if chunks.is_empty() {
+                            std::thread::sleep(POLL_INTERVAL);
+                            continue;
+                        }
+                        let auto_stop = chunks.iter().any(|c| c.auto_stop);
+                        chunks.into_iter().for_each(&mut sink);
+                        if auto_stop {
+                            break;
+                        }
+                    }
+                    Ok(())
+                })();
+                let stopped = guard.with(|| driver.stop(handle));
+                result.and(stopped)
+            })
+        };
+
+        Self {
+            stop,
+            rx,
+            worker: Some(worker),
+            time_interval,
+        }
+    }
+
+    pub fn time_interval(&self) -> Duration {
+        self.time_interval
+    }
+
+    /// Stops streaming and waits for the worker thread to finish.
+    pub fn stop(mut self) -> Result<()> {
+        self.stop.store(true, Ordering::Release);
+        self.join()
+    }
+
+    /// Waits until the stream stops automatically.
+    pub fn wait(mut self) -> Result<()> {
+        self.join()
+    }
+
+    fn join(&mut self) -> Result<()> {
+        match self.worker.take() {
+            Some(worker) => worker.join().map_err(|_| Error::ThreadPanicked)?,
+            None => Ok(()),
+        }
+    }
+}
+
+impl Iterator for StreamHandle {
+    type Item = Result<StreamChunk>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.rx.as_ref()?.recv() {
+            Ok(chunk) => Some(Ok(chunk)),
+            Err(_) => {
+                self.rx = None;
+                self.join().err().map(Err)
+            }
+        }
+    }
+}
+
+impl Drop for StreamHandle {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        let _ = self.join();
+    }
+}
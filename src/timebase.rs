@@ -0,0 +1,53 @@
+/*
+ * File: timebase.rs
+ * Project: src
+ * Created Date: 18/10/2026
+ * Author: Shun Suzuki
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: Shun Suzuki (suzuki@hapis.k.u-tokyo.ac.jp)
+ * -----
+ * Copyright (c) 2026 Shun Suzuki. All rights reserved.
+ *
+ */
+
+use std::time::Duration;
+
+/// A timebase accepted by the driver, resolved by [`crate::Ps4000Device::timebase_for_rate`]
+/// or [`crate::Ps4000Device::timebase_for_interval`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Timebase {
+    pub(crate) timebase: u32,
+    pub(crate) interval_ns: i32,
+    pub(crate) max_samples: i32,
+}
+
+impl Timebase {
+    pub(crate) fn new(timebase: u32, interval_ns: i32, max_samples: i32) -> Self {
+        Self {
+            timebase,
+            interval_ns,
+            max_samples,
+        }
+    }
+
+    /// Timebase number passed to the driver.
+    pub fn timebase(&self) -> u32 {
+        self.timebase
+    }
+
+    /// Sampling interval achieved with this timebase, as reported by the driver.
+    pub fn interval(&self) -> Duration {
+        Duration::from_nanos(self.interval_ns.max(0) as u64)
+    }
+
+    /// Sample rate in samples per second achieved with this timebase.
+    pub fn sample_rate(&self) -> f64 {
+        1e9 / self.interval_ns as f64
+    }
+
+    /// Maximum number of samples per channel with the channel setup at the time of resolving.
+    pub fn max_samples(&self) -> u32 {
+        self.max_samples.max(0) as u32
+    }
+}
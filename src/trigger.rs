@@ -4,16 +4,18 @@
  * Created Date: 15/11/2023
  * Author: Shun Suzuki
  * -----
- * Last Modified: 16/11/2023
+ * Last Modified: 18/10/2026
  * Modified By: Shun Suzuki (suzuki@hapis.k.u-tokyo.ac.jp)
  * -----
  * Copyright (c) 2023 Shun Suzuki. All rights reserved.
  *
  */
 
+use std::time::Duration;
+
 use pico_sys_dynamic::ps4000::*;
 
-use crate::Channel;
+use crate::{Channel, Error, Result};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ThresholdDirection {
@@ -67,6 +69,7 @@ impl From<ThresholdMode> for THRESHOLD_MODE {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum TriggerState {
     DoNotCare,
     True,
@@ -120,3 +123,469 @@ impl Trigger {
         self
     }
 }
+
+/// An input that can take part in an [`AdvancedTrigger`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TriggerSource {
+    A,
+    B,
+    C,
+    D,
+    External,
+    Aux,
+}
+
+impl TriggerSource {
+    pub(crate) const ALL: [TriggerSource; 6] = [
+        TriggerSource::A,
+        TriggerSource::B,
+        TriggerSource::C,
+        TriggerSource::D,
+        TriggerSource::External,
+        TriggerSource::Aux,
+    ];
+}
+
+impl From<Channel> for TriggerSource {
+    fn from(value: Channel) -> Self {
+        match value {
+            Channel::A => TriggerSource::A,
+            Channel::B => TriggerSource::B,
+            Channel::C => TriggerSource::C,
+            Channel::D => TriggerSource::D,
+        }
+    }
+}
+
+/// A set of states that must all hold for the trigger to fire.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TriggerCondition {
+    pub(crate) a: TriggerState,
+    pub(crate) b: TriggerState,
+    pub(crate) c: TriggerState,
+    pub(crate) d: TriggerState,
+    pub(crate) external: TriggerState,
+    pub(crate) aux: TriggerState,
+    pub(crate) pulse_width_qualifier: TriggerState,
+}
+
+impl TriggerCondition {
+    pub fn new() -> Self {
+        Self {
+            a: TriggerState::DoNotCare,
+            b: TriggerState::DoNotCare,
+            c: TriggerState::DoNotCare,
+            d: TriggerState::DoNotCare,
+            external: TriggerState::DoNotCare,
+            aux: TriggerState::DoNotCare,
+            pulse_width_qualifier: TriggerState::DoNotCare,
+        }
+    }
+
+    pub fn with(mut self, source: impl Into<TriggerSource>, state: TriggerState) -> Self {
+        match source.into() {
+            TriggerSource::A => self.a = state,
+            TriggerSource::B => self.b = state,
+            TriggerSource::C => self.c = state,
+            TriggerSource::D => self.d = state,
+            TriggerSource::External => self.external = state,
+            TriggerSource::Aux => self.aux = state,
+        }
+        self
+    }
+
+    /// Sets the state of the pulse width qualifier; ignored in the conditions of a [`PulseWidthQualifier`].
+    pub fn with_pulse_width_qualifier(mut self, state: TriggerState) -> Self {
+        self.pulse_width_qualifier = state;
+        self
+    }
+
+    pub(crate) fn state(&self, source: TriggerSource) -> TriggerState {
+        match source {
+            TriggerSource::A => self.a,
+            TriggerSource::B => self.b,
+            TriggerSource::C => self.c,
+            TriggerSource::D => self.d,
+            TriggerSource::External => self.external,
+            TriggerSource::Aux => self.aux,
+        }
+    }
+}
+
+impl Default for TriggerCondition {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl From<TriggerCondition> for TRIGGER_CONDITIONS {
+    fn from(value: TriggerCondition) -> Self {
+        Self {
+            channelA: value.a.into(),
+            channelB: value.b.into(),
+            channelC: value.c.into(),
+            channelD: value.d.into(),
+            external: value.external.into(),
+            aux: value.aux.into(),
+            pulseWidthQualifier: value.pulse_width_qualifier.into(),
+        }
+    }
+}
+
+impl From<TriggerCondition> for PWQ_CONDITIONS {
+    fn from(value: TriggerCondition) -> Self {
+        Self {
+            channelA: value.a.into(),
+            channelB: value.b.into(),
+            channelC: value.c.into(),
+            channelD: value.d.into(),
+            external: value.external.into(),
+            aux: value.aux.into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PulseWidthType {
+    LessThan,
+    GreaterThan,
+    InRange,
+    OutOfRange,
+}
+
+impl From<PulseWidthType> for PULSE_WIDTH_TYPE {
+    fn from(value: PulseWidthType) -> Self {
+        match value {
+            PulseWidthType::LessThan => enPulseWidthType_PW_TYPE_LESS_THAN,
+            PulseWidthType::GreaterThan => enPulseWidthType_PW_TYPE_GREATER_THAN,
+            PulseWidthType::InRange => enPulseWidthType_PW_TYPE_IN_RANGE,
+            PulseWidthType::OutOfRange => enPulseWidthType_PW_TYPE_OUT_OF_RANGE,
+        }
+    }
+}
+
+/// Qualifies an [`AdvancedTrigger`] by the width of a pulse.
+///
+/// The pulse starts when the conditions become true in `direction` and ends when they become false again.
+/// If no condition is added, the conditions of the trigger are used.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PulseWidthQualifier {
+    pub(crate) ty: PulseWidthType,
+    pub(crate) lower: Duration,
+    pub(crate) upper: Duration,
+    pub(crate) dir: ThresholdDirection,
+    pub(crate) conditions: Vec<TriggerCondition>,
+}
+
+impl PulseWidthQualifier {
+    pub fn new(ty: PulseWidthType, lower: Duration, upper: Duration) -> Self {
+        Self {
+            ty,
+            lower,
+            upper,
+            dir: ThresholdDirection::Rising,
+            conditions: Vec::new(),
+        }
+    }
+
+    pub fn less_than(width: Duration) -> Self {
+        Self::new(PulseWidthType::LessThan, width, Duration::ZERO)
+    }
+
+    pub fn greater_than(width: Duration) -> Self {
+        Self::new(PulseWidthType::GreaterThan, width, Duration::ZERO)
+    }
+
+    pub fn in_range(lower: Duration, upper: Duration) -> Self {
+        Self::new(PulseWidthType::InRange, lower, upper)
+    }
+
+    pub fn out_of_range(lower: Duration, upper: Duration) -> Self {
+        Self::new(PulseWidthType::OutOfRange, lower, upper)
+    }
+
+    /// Sets the direction in which the pulse starts; `Rising` qualifies positive pulses and `Falling` negative ones.
+    pub fn with_direction(mut self, dir: ThresholdDirection) -> Self {
+        self.dir = dir;
+        self
+    }
+
+    pub fn with_condition(mut self, condition: TriggerCondition) -> Self {
+        self.conditions.push(condition);
+        self
+    }
+
+    /// Returns the lower and upper widths in samples of `time_interval_ns`.
+    pub(crate) fn widths(&self, time_interval_ns: i32) -> Result<(u32, u32)> {
+        let to_samples =
+            |width: Duration| (width.as_nanos() as f64 / time_interval_ns as f64).round() as u32;
+        let lower = to_samples(self.lower);
+        let upper = to_samples(self.upper);
+        if lower == 0 {
+            return Err(Error::InvalidConfig(format!(
+                "pulse width {:?} is shorter than the sample interval of {} ns",
+                self.lower, time_interval_ns
+            )));
+        }
+        if matches!(
+            self.ty,
+            PulseWidthType::InRange | PulseWidthType::OutOfRange
+        ) && lower >= upper
+        {
+            return Err(Error::InvalidConfig(format!(
+                "pulse widths {:?} and {:?} do not form a range at {} ns per sample",
+                self.lower, self.upper, time_interval_ns
+            )));
+        }
+        Ok((lower, upper))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct ChannelThreshold {
+    pub(crate) channel: Channel,
+    pub(crate) mode: ThresholdMode,
+    pub(crate) dir: ThresholdDirection,
+    pub(crate) upper_mv: f64,
+    pub(crate) lower_mv: f64,
+    pub(crate) hysteresis_mv: f64,
+}
+
+impl ChannelThreshold {
+    pub(crate) fn validate(&self) -> Result<()> {
+        use ThresholdDirection::*;
+        let valid = match self.mode {
+            ThresholdMode::Level => {
+                matches!(self.dir, Above | Below | Rising | Falling | RisingOrFalling)
+            }
+            ThresholdMode::Window => {
+                matches!(self.dir, Inside | Outside | Enter | Exit | EnterOrExit)
+                    && self.lower_mv < self.upper_mv
+            }
+        };
+        if !valid {
+            return Err(Error::InvalidConfig(format!(
+                "{:?} threshold on channel {:?} cannot be used with {:?}",
+                self.mode, self.channel, self.dir
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// A trigger combining thresholds on several inputs.
+///
+/// Each [`TriggerCondition`] is the AND of its states, and the trigger fires when any of the conditions holds.
+/// If no condition is added, all configured inputs must fire together.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdvancedTrigger {
+    pub(crate) thresholds: Vec<ChannelThreshold>,
+    pub(crate) ext_dir: Option<ThresholdDirection>,
+    pub(crate) aux_dir: Option<ThresholdDirection>,
+    pub(crate) conditions: Vec<TriggerCondition>,
+    pub(crate) pulse_width_qualifier: Option<PulseWidthQualifier>,
+    pub(crate) no_of_pre_trigger_samples: i32,
+    pub(crate) delay: u32,
+    pub(crate) auto_trigger_ms: i32,
+}
+
+impl AdvancedTrigger {
+    pub fn new() -> Self {
+        Self {
+            thresholds: Vec::new(),
+            ext_dir: None,
+            aux_dir: None,
+            conditions: Vec::new(),
+            pulse_width_qualifier: None,
+            no_of_pre_trigger_samples: 0,
+            delay: 0,
+            auto_trigger_ms: 0,
+        }
+    }
+
+    fn with_threshold(mut self, threshold: ChannelThreshold) -> Self {
+        self.thresholds.retain(|t| t.channel != threshold.channel);
+        self.thresholds.push(threshold);
+        self
+    }
+
+    /// Fires when `channel` crosses `value_mv` in direction `dir`.
+    pub fn with_level(self, channel: Channel, dir: ThresholdDirection, value_mv: f64) -> Self {
+        self.with_threshold(ChannelThreshold {
+            channel,
+            mode: ThresholdMode::Level,
+            dir,
+            upper_mv: value_mv,
+            lower_mv: value_mv,
+            hysteresis_mv: 0.,
+        })
+    }
+
+    /// Fires when `channel` is inside, outside, enters or leaves the window between `lower_mv` and `upper_mv`.
+    pub fn with_window(
+        self,
+        channel: Channel,
+        dir: ThresholdDirection,
+        lower_mv: f64,
+        upper_mv: f64,
+    ) -> Self {
+        self.with_threshold(ChannelThreshold {
+            channel,
+            mode: ThresholdMode::Window,
+            dir,
+            upper_mv,
+            lower_mv,
+            hysteresis_mv: 0.,
+        })
+    }
+
+    /// Sets the hysteresis of the thresholds of `channel`, which must be configured beforehand.
+    pub fn with_hysteresis_mv(mut self, channel: Channel, hysteresis_mv: f64) -> Self {
+        self.thresholds
+            .iter_mut()
+            .filter(|t| t.channel == channel)
+            .for_each(|t| t.hysteresis_mv = hysteresis_mv);
+        self
+    }
+
+    /// Triggers on the external trigger input, whose threshold is not adjustable.
+    pub fn with_external(mut self, dir: ThresholdDirection) -> Self {
+        self.ext_dir = Some(dir);
+        self
+    }
+
+    pub fn with_aux(mut self, dir: ThresholdDirection) -> Self {
+        self.aux_dir = Some(dir);
+        self
+    }
+
+    pub fn with_condition(mut self, condition: TriggerCondition) -> Self {
+        self.conditions.push(condition);
+        self
+    }
+
+    /// Qualifies the trigger by a pulse width; the qualifier is required by every condition that does not set its state.
+    pub fn with_pulse_width_qualifier(
+        mut self,
+        pulse_width_qualifier: PulseWidthQualifier,
+    ) -> Self {
+        self.pulse_width_qualifier = Some(pulse_width_qualifier);
+        self
+    }
+
+    pub fn with_no_of_pre_trigger_samples(mut self, no_of_pre_trigger_samples: i32) -> Self {
+        self.no_of_pre_trigger_samples = no_of_pre_trigger_samples;
+        self
+    }
+
+    pub fn with_delay(mut self, delay: u32) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    pub fn with_auto_trigger_ms(mut self, auto_trigger_ms: i32) -> Self {
+        self.auto_trigger_ms = auto_trigger_ms;
+        self
+    }
+
+    pub(crate) fn direction(&self, source: TriggerSource) -> ThresholdDirection {
+        match source {
+            TriggerSource::External => self.ext_dir,
+            TriggerSource::Aux => self.aux_dir,
+            source => self
+                .thresholds
+                .iter()
+                .find(|t| TriggerSource::from(t.channel) == source)
+                .map(|t| t.dir),
+        }
+        .unwrap_or(ThresholdDirection::None)
+    }
+
+    fn resolve(&self, conditions: &[TriggerCondition]) -> Result<Vec<TriggerCondition>> {
+        let configured = |source: TriggerSource| match source {
+            TriggerSource::External => self.ext_dir.is_some(),
+            TriggerSource::Aux => self.aux_dir.is_some(),
+            source => self
+                .thresholds
+                .iter()
+                .any(|t| TriggerSource::from(t.channel) == source),
+        };
+        let sources = TriggerSource::ALL;
+
+        if conditions.is_empty() {
+            return Ok(vec![sources
+                .into_iter()
+                .filter(|&source| configured(source))
+                .fold(TriggerCondition::new(), |cond, source| {
+                    cond.with(source, TriggerState::True)
+                })]);
+        }
+
+        for cond in conditions.iter() {
+            if let Some(source) = sources.into_iter().find(|&source| {
+                cond.state(source) != TriggerState::DoNotCare && !configured(source)
+            }) {
+                return Err(Error::InvalidConfig(format!(
+                    "{:?} is used in a trigger condition but has no threshold",
+                    source
+                )));
+            }
+        }
+        Ok(conditions.to_vec())
+    }
+
+    pub(crate) fn conditions(&self) -> Result<Vec<TriggerCondition>> {
+        let mut conditions = self.resolve(&self.conditions)?;
+        if self.pulse_width_qualifier.is_some() {
+            conditions
+                .iter_mut()
+                .filter(|cond| cond.pulse_width_qualifier == TriggerState::DoNotCare)
+                .for_each(|cond| cond.pulse_width_qualifier = TriggerState::True);
+        }
+        Ok(conditions)
+    }
+
+    pub(crate) fn pulse_width_conditions(&self) -> Result<Vec<TriggerCondition>> {
+        match &self.pulse_width_qualifier {
+            Some(pwq) => match pwq.conditions.is_empty() {
+                true => self.resolve(&self.conditions),
+                false => self.resolve(&pwq.conditions),
+            },
+            None => Ok(Vec::new()),
+        }
+    }
+}
+
+impl Default for AdvancedTrigger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Either a [`Trigger`] or an [`AdvancedTrigger`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum TriggerConfig {
+    Simple(Trigger),
+    Advanced(AdvancedTrigger),
+}
+
+impl TriggerConfig {
+    pub(crate) fn no_of_pre_trigger_samples(&self) -> i32 {
+        match self {
+            TriggerConfig::Simple(trigger) => trigger.no_of_pre_trigger_samples,
+            TriggerConfig::Advanced(trigger) => trigger.no_of_pre_trigger_samples,
+        }
+    }
+}
+
+impl From<Trigger> for TriggerConfig {
+    fn from(value: Trigger) -> Self {
+        TriggerConfig::Simple(value)
+    }
+}
+
+impl From<AdvancedTrigger> for TriggerConfig {
+    fn from(value: AdvancedTrigger) -> Self {
+        TriggerConfig::Advanced(value)
+    }
+}
@@ -0,0 +1,211 @@
+/*
+ * File: unit.rs
+ * Project: src
+ * Created Date: 18/10/2026
+ * Author: Shun Suzuki
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: Shun Suzuki (suzuki@hapis.k.u-tokyo.ac.jp)
+ * -----
+ * Copyright (c) 2026 Shun Suzuki. All rights reserved.
+ *
+ */
+
+use std::{
+    future::Future,
+    marker::PhantomData,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+
+use pico_common::{PicoInfo, PicoStatus};
+
+use crate::{
+    driver::{Ps4000Driver, Ps4000Library},
+    ps4000::Ps4000Device,
+    Error, Result,
+};
+
+/// A unit found by [`enumerate_units`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnumeratedUnit {
+    pub serial: String,
+    /// Model number such as `4262`, or `None` if the unit could not be opened to query it.
+    pub variant: Option<String>,
+}
+
+/// Identification of an opened unit, returned by [`Ps4000Device::info`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnitInfo {
+    pub driver_version: String,
+    pub usb_version: String,
+    pub hardware_version: String,
+    pub variant: String,
+    pub batch_and_serial: String,
+    pub cal_date: String,
+    pub kernel_version: String,
+    pub firmware_version: String,
+}
+
+impl UnitInfo {
+    pub(crate) fn query<D: Ps4000Driver>(driver: &D, handle: i16) -> Result<Self> {
+        let info = |info| driver.get_unit_info(handle, info);
+        Ok(Self {
+            driver_version: info(PicoInfo::DRIVER_VERSION)?,
+            usb_version: info(PicoInfo::USB_VERSION)?,
+            hardware_version: info(PicoInfo::HARDWARE_VERSION)?,
+            variant: info(PicoInfo::VARIANT_INFO)?,
+            batch_and_serial: info(PicoInfo::BATCH_AND_SERIAL)?,
+            cal_date: info(PicoInfo::CAL_DATE)?,
+            kernel_version: info(PicoInfo::KERNEL_VERSION)?,
+            firmware_version: info(PicoInfo::FIRMWARE_VERSION_1)?,
+        })
+    }
+}
+
+/// Lists the units attached to this computer that are not opened yet.
+pub fn enumerate_units() -> Result<Vec<EnumeratedUnit>> {
+    enumerate_units_with(&Ps4000Library::new()?)
+}
+
+/// Lists the units that are not opened yet using `driver`.
+///
+/// Each unit is opened briefly to read its variant.
+pub fn enumerate_units_with<D: Ps4000Driver>(driver: &D) -> Result<Vec<EnumeratedUnit>> {
+    Ok(driver
+        .enumerate_units()?
+        .into_iter()
+        .map(|serial| {
+            let variant = driver.open_unit_ex(&serial).ok().and_then(|handle| {
+                let variant = driver.get_unit_info(handle, PicoInfo::VARIANT_INFO).ok();
+                let _ = driver.close_unit(handle);
+                variant
+            });
+            EnumeratedUnit { serial, variant }
+        })
+        .collect())
+}
+
+/// A unit being opened in the background, returned by [`Ps4000Device::open_async`] and [`crate::PS4262::open_async`].
+///
+/// Loading the firmware takes several seconds.
+/// Poll [`PendingOpen::progress`] and [`PendingOpen::try_finish`] from an event loop, block on [`PendingOpen::wait`], or `.await` it.
+/// Dropping it after opening has completed closes the unit.
+pub struct PendingOpen<D: Ps4000Driver + 'static = Ps4000Library, T = Ps4000Device<D>> {
+    driver: Option<D>,
+    handle: Option<i16>,
+    progress: i16,
+    // shared with the thread that wakes the task while it is awaited
+    waker: Option<Arc<Mutex<Option<Waker>>>>,
+    _device: PhantomData<fn() -> T>,
+}
+
+impl<D, T> PendingOpen<D, T>
+where
+    D: Ps4000Driver + 'static,
+    T: TryFrom<Ps4000Device<D>>,
+    Error: From<T::Error>,
+{
+    const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+    pub(crate) fn new(driver: D) -> Self {
+        Self {
+            driver: Some(driver),
+            handle: None,
+            progress: 0,
+            waker: None,
+            _device: PhantomData,
+        }
+    }
+
+    /// Queries the driver and returns the progress in percent.
+    pub fn progress(&mut self) -> Result<i16> {
+        if self.handle.is_some() {
+            return Ok(self.progress);
+        }
+        let driver = self.driver.as_ref().ok_or(Error::AlreadyReturned)?;
+        let (handle, progress, complete) = driver.open_unit_progress()?;
+        self.progress = progress;
+        if complete {
+            if handle <= 0 {
+                return Err(Error::driver(
+                    "ps4000OpenUnitProgress",
+                    PicoStatus::NOT_FOUND,
+                ));
+            }
+            self.handle = Some(handle);
+        }
+        Ok(self.progress)
+    }
+
+    /// Returns the opened unit once complete, or `None` while the firmware is still loading.
+    ///
+    /// Returns [`Error::AlreadyReturned`] if called again after the unit has been returned.
+    pub fn try_finish(&mut self) -> Result<Option<T>> {
+        self.progress()?;
+        match self.handle.take() {
+            Some(handle) => {
+                let driver = self.driver.take().ok_or(Error::AlreadyReturned)?;
+                Ok(Some(Ps4000Device::from_handle(driver, handle)?.try_into()?))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Blocks until the unit is opened.
+    pub fn wait(mut self) -> Result<T> {
+        loop {
+            if let Some(pico) = self.try_finish()? {
+                return Ok(pico);
+            }
+            std::thread::sleep(Self::POLL_INTERVAL);
+        }
+    }
+}
+
+impl<D, T> Future for PendingOpen<D, T>
+where
+    D: Ps4000Driver + Unpin + 'static,
+    T: TryFrom<Ps4000Device<D>>,
+    Error: From<T::Error>,
+{
+    type Output = Result<T>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        match this.try_finish() {
+            Ok(Some(pico)) => Poll::Ready(Ok(pico)),
+            Ok(None) => {
+                // the driver has no completion notification, so one thread wakes the latest waker after each interval
+                let waker = this.waker.get_or_insert_with(|| {
+                    let waker = Arc::new(Mutex::new(None::<Waker>));
+                    let weak = Arc::downgrade(&waker);
+                    std::thread::spawn(move || loop {
+                        std::thread::sleep(Self::POLL_INTERVAL);
+                        let Some(waker) = weak.upgrade() else {
+                            return;
+                        };
+                        let waker = waker.lock().unwrap().take();
+                        if let Some(waker) = waker {
+                            waker.wake();
+                        }
+                    });
+                    waker
+                });
+                *waker.lock().unwrap() = Some(cx.waker().clone());
+                Poll::Pending
+            }
+            Err(e) => Poll::Ready(Err(e)),
+        }
+    }
+}
+
+impl<D: Ps4000Driver + 'static, T> Drop for PendingOpen<D, T> {
+    fn drop(&mut self) {
+        if let (Some(driver), Some(handle)) = (&self.driver, self.handle) {
+            let _ = driver.close_unit(handle);
+        }
+    }
+}
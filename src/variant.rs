@@ -0,0 +1,310 @@
+/*
+ * File: variant.rs
+ * Project: src
+ * Created Date: 18/10/2026
+ * Author: Shun Suzuki
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: Shun Suzuki (suzuki@hapis.k.u-tokyo.ac.jp)
+ * -----
+ * Copyright (c) 2026 Shun Suzuki. All rights reserved.
+ *
+ */
+
+use std::{fmt, str::FromStr};
+
+use pico_sys_dynamic::ps4000::{PS4000_MAX_VALUE, PS4000_RANGE, PS4262_MAX_VALUE};
+
+use crate::{attenuation::Attenuation, error::Error, range::Range, trigger::TriggerSource, Result};
+
+const RANGES_50MV_TO_20V: &[Range] = &[
+    Range::Range50mv,
+    Range::Range100mv,
+    Range::Range200mv,
+    Range::Range500mv,
+    Range::Range1v,
+    Range::Range2v,
+    Range::Range5v,
+    Range::Range10v,
+    Range::Range20v,
+];
+
+const RANGES_50MV_TO_100V: &[Range] = &[
+    Range::Range50mv,
+    Range::Range100mv,
+    Range::Range200mv,
+    Range::Range500mv,
+    Range::Range1v,
+    Range::Range2v,
+    Range::Range5v,
+    Range::Range10v,
+    Range::Range20v,
+    Range::Range50v,
+    Range::Range100v,
+];
+
+const RANGES_10MV_TO_20V: &[Range] = &[
+    Range::Range10mv,
+    Range::Range20mv,
+    Range::Range50mv,
+    Range::Range100mv,
+    Range::Range200mv,
+    Range::Range500mv,
+    Range::Range1v,
+    Range::Range2v,
+    Range::Range5v,
+    Range::Range10v,
+    Range::Range20v,
+];
+
+/// A model of the PicoScope 4000 series, as reported by the unit after opening.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Variant {
+    PS4223,
+    PS4224,
+    PS4226,
+    PS4227,
+    PS4262,
+    PS4423,
+    PS4424,
+}
+
+impl Variant {
+    pub fn channel_count(&self) -> usize {
+        match self {
+            Variant::PS4423 | Variant::PS4424 => 4,
+            _ => 2,
+        }
+    }
+
+    /// Whether `source` is an input of this model that can take part in a trigger.
+    pub fn has_trigger_source(&self, source: TriggerSource) -> bool {
+        match source {
+            TriggerSource::A | TriggerSource::B => true,
+            TriggerSource::C | TriggerSource::D => self.channel_count() == 4,
+            TriggerSource::External => {
+                matches!(self, Variant::PS4226 | Variant::PS4227 | Variant::PS4262)
+            }
+            // no model of the series has an AUX input
+            TriggerSource::Aux => false,
+        }
+    }
+
+    /// ADC count corresponding to the full scale of the input range.
+    pub fn max_value(&self) -> u32 {
+        match self {
+            Variant::PS4262 => PS4262_MAX_VALUE,
+            _ => PS4000_MAX_VALUE,
+        }
+    }
+
+    /// Input ranges available with a X1 probe.
+    pub fn ranges(&self) -> &'static [Range] {
+        match self {
+            Variant::PS4223 | Variant::PS4423 => RANGES_50MV_TO_100V,
+            Variant::PS4262 => RANGES_10MV_TO_20V,
+            _ => RANGES_50MV_TO_20V,
+        }
+    }
+
+    /// Highest frequency of the signal generator in Hz, or `None` if this model has no signal generator.
+    pub fn sig_gen_max_frequency(&self) -> Option<f32> {
+        match self {
+            Variant::PS4226 | Variant::PS4227 => Some(100_000.),
+            Variant::PS4262 => Some(20_000.),
+            _ => None,
+        }
+    }
+
+    /// Largest output voltage of the signal generator in microvolts including the offset,
+    /// or `None` if this model has no signal generator.
+    pub fn sig_gen_max_voltage_uv(&self) -> Option<u32> {
+        self.sig_gen_max_frequency().map(|_| 2_000_000)
+    }
+
+    pub(crate) fn supports_range(&self, range: PS4000_RANGE) -> bool {
+        self.ranges()
+            .iter()
+            .any(|r| r.into_range(Attenuation::X1).ok() == Some(range))
+    }
+
+    pub(crate) fn validate_range(&self, range: Range, attenuation: Attenuation) -> Result<()> {
+        if !self.supports_range(range.into_range(attenuation)?) {
+            return Err(Error::InvalidConfig(format!(
+                "{:?} with {:?} probe is not available on {}",
+                range, attenuation, self
+            )));
+        }
+        Ok(())
+    }
+
+    // Number of timebases in the power-of-two region, its clock, and the clock of the linear region.
+    fn clocks(&self) -> (u32, f64, f64) {
+        match self {
+            Variant::PS4223 | Variant::PS4224 | Variant::PS4423 | Variant::PS4424 => {
+                (2, 80e6, 20e6)
+            }
+            Variant::PS4226 | Variant::PS4227 => (3, 250e6, 31.25e6),
+            Variant::PS4262 => (0, 0., 10e6),
+        }
+    }
+
+    /// Slowest timebase accepted by the driver.
+    pub(crate) fn max_timebase(&self) -> u32 {
+        (1 << 30) - 1
+    }
+
+    /// Sampling interval in seconds of `timebase`.
+    pub(crate) fn interval(&self, timebase: u32) -> f64 {
+        let (fast, fast_hz, slow_hz) = self.clocks();
+        if timebase < fast {
+            (1u32 << timebase) as f64 / fast_hz
+        } else {
+            ((timebase - fast) as f64 + 1.) / slow_hz
+        }
+    }
+
+    /// The slowest timebase whose interval does not exceed `interval` seconds.
+    pub(crate) fn timebase_for_interval(&self, interval: f64) -> u32 {
+        // tolerate rounding so that exact intervals map onto their own timebase
+        let interval = interval * (1. + 1e-9);
+        let (fast, _, slow_hz) = self.clocks();
+        let slow = (interval * slow_hz).floor();
+        if slow >= 1. {
+            return (slow.min(u32::MAX as f64) as u32 - 1).saturating_add(fast);
+        }
+        (0..fast)
+            .rev()
+            .find(|&n| self.interval(n) <= interval)
+            .unwrap_or(0)
+    }
+
+    /// The timebase whose interval is nearest to `interval` seconds, or the slowest one for longer intervals.
+    pub(crate) fn nearest_timebase(&self, interval: f64) -> u32 {
+        let faster = self.timebase_for_interval(interval);
+        if faster >= self.max_timebase() {
+            return self.max_timebase();
+        }
+        let slower = faster + 1;
+        if self.interval(slower) - interval < interval - self.interval(faster) {
+            slower
+        } else {
+            faster
+        }
+    }
+}
+
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Variant::PS4223 => "4223",
+            Variant::PS4224 => "4224",
+            Variant::PS4226 => "4226",
+            Variant::PS4227 => "4227",
+            Variant::PS4262 => "4262",
+            Variant::PS4423 => "4423",
+            Variant::PS4424 => "4424",
+        };
+        write!(f, "PicoScope {}", name)
+    }
+}
+
+impl FromStr for Variant {
+    type Err = Error;
+
+    /// Parses the variant string reported by the driver, such as `4262`.
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim() {
+            "4223" => Ok(Variant::PS4223),
+            "4224" => Ok(Variant::PS4224),
+            "4226" => Ok(Variant::PS4226),
+            "4227" => Ok(Variant::PS4227),
+            "4262" => Ok(Variant::PS4262),
+            "4423" => Ok(Variant::PS4423),
+            "4424" => Ok(Variant::PS4424),
+            s => Err(Error::UnsupportedVariant(s.to_string())),
+        }
+    }
+}
+
+pub(crate) fn convert_adc_to_mv(
+    raw: i16,
+    max_value: u32,
+    attenuation: Attenuation,
+    range_mv: i32,
+) -> f64 {
+    (raw as i32 * range_mv) as f64 / max_value as f64 * attenuation.value()
+}
+
+pub(crate) fn convert_mv_to_adc(
+    raw: f64,
+    max_value: u32,
+    attenuation: Attenuation,
+    range_mv: i32,
+) -> i16 {
+    (raw / attenuation.value() * max_value as f64 / range_mv as f64) as i16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NS: f64 = 1e-9;
+
+    fn assert_interval(variant: Variant, timebase: u32, interval_ns: f64) {
+        assert!((variant.interval(timebase) / NS - interval_ns).abs() < 1e-6);
+    }
+
+    #[test]
+    fn intervals_of_the_4226_cross_from_powers_of_two_to_the_linear_region() {
+        let v = Variant::PS4226;
+        assert_interval(v, 0, 4.);
+        assert_interval(v, 2, 16.);
+        assert_interval(v, 3, 32.);
+        assert_interval(v, 4, 64.);
+
+        assert_eq!(v.timebase_for_interval(16. * NS), 2);
+        assert_eq!(v.timebase_for_interval(31. * NS), 2);
+        assert_eq!(v.timebase_for_interval(32. * NS), 3);
+        assert_eq!(v.nearest_timebase(23. * NS), 2);
+        assert_eq!(v.nearest_timebase(25. * NS), 3);
+    }
+
+    #[test]
+    fn intervals_of_the_4224_cross_from_powers_of_two_to_the_linear_region() {
+        for v in [Variant::PS4224, Variant::PS4424] {
+            assert_interval(v, 0, 12.5);
+            assert_interval(v, 1, 25.);
+            assert_interval(v, 2, 50.);
+            assert_interval(v, 3, 100.);
+
+            assert_eq!(v.timebase_for_interval(25. * NS), 1);
+            assert_eq!(v.timebase_for_interval(49. * NS), 1);
+            assert_eq!(v.timebase_for_interval(50. * NS), 2);
+            assert_eq!(v.nearest_timebase(40. * NS), 2);
+            assert_eq!(v.nearest_timebase(35. * NS), 1);
+        }
+    }
+
+    #[test]
+    fn intervals_of_the_4262_are_linear() {
+        let v = Variant::PS4262;
+        assert_interval(v, 0, 100.);
+        assert_interval(v, 9, 1_000.);
+
+        assert_eq!(v.timebase_for_interval(1e-6), 9);
+        assert_eq!(v.nearest_timebase(1e-6), 9);
+        assert_eq!(v.nearest_timebase(1.04e-6), 9);
+        assert_eq!(v.nearest_timebase(1.06e-6), 10);
+    }
+
+    #[test]
+    fn intervals_beyond_the_limits_use_the_fastest_or_slowest_timebase() {
+        for v in [Variant::PS4224, Variant::PS4226, Variant::PS4262] {
+            assert_eq!(v.timebase_for_interval(1e-12), 0);
+            assert_eq!(v.nearest_timebase(1e-12), 0);
+            assert_eq!(v.nearest_timebase(1e3), v.max_timebase());
+            assert_eq!(v.nearest_timebase(f64::MAX), v.max_timebase());
+        }
+    }
+}
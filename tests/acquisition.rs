@@ -0,0 +1,120 @@
+/*
+ * File: acquisition.rs
+ * Project: tests
+ * Created Date: 18/10/2026
+ * Author: Shun Suzuki
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: Shun Suzuki (suzuki@hapis.k.u-tokyo.ac.jp)
+ * -----
+ * Copyright (c) 2026 Shun Suzuki. All rights reserved.
+ *
+ */
+
+use std::time::{Duration, Instant};
+
+use ps4000lib::*;
+
+const SAMPLE_RATE: u32 = 1_000_000;
+
+fn open(sim: SimulatedPs4262) -> PS4262<SimulatedPs4262> {
+    PS4262::with_driver(sim).unwrap()
+}
+
+fn never_firing() -> Trigger {
+    Trigger::new(Channel::A, ThresholdDirection::Rising, 1_000.0)
+}
+
+#[test]
+fn pending_capture_is_superseded_by_the_next_capture() {
+    let ps = open(SimulatedPs4262::new().with_signal(Channel::A, Signal::dc(0.0)));
+
+    let pending = ps
+        .collect_block_triggered(1000, SAMPLE_RATE, never_firing())
+        .unwrap();
+    let data = ps.collect_block_immediate(1000, SAMPLE_RATE).unwrap();
+
+    assert_eq!(data.len(), 1000);
+    assert!(matches!(pending.wait(), Err(Error::Superseded)));
+}
+
+#[test]
+fn never_firing_trigger_can_be_cancelled() {
+    let ps = open(SimulatedPs4262::new().with_signal(Channel::A, Signal::dc(0.0)));
+
+    let start = Instant::now();
+    let handle = ps
+        .collect_block_triggered(1000, SAMPLE_RATE, never_firing())
+        .unwrap();
+    assert!(!handle.is_ready());
+    handle.cancel().unwrap();
+
+    assert!(start.elapsed() < Duration::from_secs(1));
+}
+
+#[test]
+fn wait_timeout_can_be_retried_and_then_fails() {
+    let ps = open(SimulatedPs4262::new().with_signal(Channel::A, Signal::dc(0.0)));
+
+    let mut handle = ps
+        .collect_block_triggered(1000, SAMPLE_RATE, never_firing())
+        .unwrap();
+
+    assert!(matches!(
+        handle.wait_timeout(Duration::from_millis(50)),
+        Err(Error::Timeout)
+    ));
+    assert!(matches!(
+        handle.wait_timeout(Duration::from_millis(50)),
+        Err(Error::Timeout)
+    ));
+    handle.cancel().unwrap();
+}
+
+#[test]
+fn waiting_again_after_the_data_is_returned_fails() {
+    let ps = open(SimulatedPs4262::new());
+
+    let mut handle = ps
+        .collect_block_triggered(
+            1000,
+            SAMPLE_RATE,
+            Trigger::new(Channel::A, ThresholdDirection::Rising, 0.0),
+        )
+        .unwrap();
+
+    assert_eq!(
+        handle.wait_timeout(Duration::from_secs(5)).unwrap().len(),
+        1000
+    );
+    assert!(matches!(
+        handle.wait_timeout(Duration::from_secs(5)),
+        Err(Error::AlreadyReturned)
+    ));
+}
+
+#[test]
+fn dropping_a_superseded_handle_does_not_stop_the_next_capture() {
+    let ps = open(SimulatedPs4262::new().with_signal(Channel::A, Signal::square(1_000.0, 1_000.0)));
+
+    let first = ps
+        .collect_block_triggered(
+            1000,
+            SAMPLE_RATE,
+            Trigger::new(Channel::A, ThresholdDirection::Rising, 5_000.0),
+        )
+        .unwrap();
+    let mut second = ps
+        .collect_block_triggered(
+            1000,
+            SAMPLE_RATE,
+            Trigger::new(Channel::A, ThresholdDirection::Rising, 0.0),
+        )
+        .unwrap();
+    drop(first);
+
+    assert_eq!(
+        second.wait_timeout(Duration::from_secs(5)).unwrap().len(),
+        1000
+    );
+}
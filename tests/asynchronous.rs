@@ -0,0 +1,131 @@
+/*
+ * File: asynchronous.rs
+ * Project: tests
+ * Created Date: 18/10/2026
+ * Author: Shun Suzuki
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: Shun Suzuki (suzuki@hapis.k.u-tokyo.ac.jp)
+ * -----
+ * Copyright (c) 2026 Shun Suzuki. All rights reserved.
+ *
+ */
+
+#![cfg(feature = "async")]
+
+use std::{
+    future::Future,
+    pin::{pin, Pin},
+    sync::Arc,
+    task::{Context, Poll, Wake, Waker},
+    thread::Thread,
+};
+
+use futures_core::Stream;
+use ps4000lib::*;
+
+const SAMPLE_RATE: u32 = 1_000_000;
+
+struct Unpark(Thread);
+
+impl Wake for Unpark {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+// Polls `fut` on the current thread, parking it until the future is woken.
+fn block_on<F: Future>(fut: F) -> F::Output {
+    let mut fut = pin!(fut);
+    let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        if let Poll::Ready(output) = fut.as_mut().poll(&mut cx) {
+            return output;
+        }
+        std::thread::park();
+    }
+}
+
+fn next<S: Stream + Unpin>(stream: &mut S) -> Option<S::Item> {
+    block_on(std::future::poll_fn(|cx| {
+        Pin::new(&mut *stream).poll_next(cx)
+    }))
+}
+
+fn open(sim: SimulatedPs4262) -> PS4262<SimulatedPs4262> {
+    let mut ps = PS4262::with_driver(sim).unwrap();
+    ps[Channel::A].range = Range::Range2v;
+    ps
+}
+
+fn rising() -> Trigger {
+    Trigger::new(Channel::A, ThresholdDirection::Rising, 0.0).with_no_of_pre_trigger_samples(100)
+}
+
+fn mean(data: &BlockData, range: std::ops::Range<usize>) -> f64 {
+    let len = range.len() as f64;
+    data.get_mv(Channel::A)
+        .skip(range.start)
+        .take(range.len())
+        .sum::<f64>()
+        / len
+}
+
+#[test]
+fn triggered_capture_completes_when_awaited() {
+    let ps = open(SimulatedPs4262::new().with_signal(Channel::A, Signal::square(1_000.0, 1_000.0)));
+
+    let data = block_on(
+        ps.collect_block_triggered_async(1000, SAMPLE_RATE, rising())
+            .unwrap(),
+    )
+    .unwrap();
+
+    assert_eq!(data.len(), 1000);
+    assert!(mean(&data, 0..90) < -900.0);
+    assert!(mean(&data, 110..200) > 900.0);
+}
+
+#[test]
+fn dropping_the_device_completes_the_future() {
+    let ps = open(SimulatedPs4262::new().with_signal(Channel::A, Signal::dc(0.0)));
+    let never_firing = Trigger::new(Channel::A, ThresholdDirection::Rising, 1_000.0);
+    let mut pending = ps
+        .collect_block_triggered_async(1000, SAMPLE_RATE, never_firing)
+        .unwrap();
+    let mut cx = Context::from_waker(Waker::noop());
+    assert!(Pin::new(&mut pending).poll(&mut cx).is_pending());
+
+    drop(ps);
+
+    assert!(matches!(block_on(pending), Err(Error::DeviceClosed)));
+}
+
+#[test]
+fn dropped_future_stops_its_capture() {
+    let ps = open(SimulatedPs4262::new().with_signal(Channel::A, Signal::dc(0.0)));
+    let never_firing = Trigger::new(Channel::A, ThresholdDirection::Rising, 1_000.0);
+    let pending = ps
+        .collect_block_triggered_async(1000, SAMPLE_RATE, never_firing)
+        .unwrap();
+
+    drop(pending);
+
+    assert_eq!(
+        ps.collect_block_immediate(1000, SAMPLE_RATE).unwrap().len(),
+        1000
+    );
+}
+
+#[test]
+fn block_stream_yields_one_triggered_block_after_another() {
+    let ps = open(SimulatedPs4262::new().with_signal(Channel::A, Signal::square(1_000.0, 1_000.0)));
+    let mut stream = ps.block_stream(1000, SAMPLE_RATE, rising());
+
+    for _ in 0..3 {
+        let data = next(&mut stream).unwrap().unwrap();
+        assert_eq!(data.len(), 1000);
+        assert!(mean(&data, 110..200) > 900.0);
+    }
+}
@@ -0,0 +1,106 @@
+/*
+ * File: block_data.rs
+ * Project: tests
+ * Created Date: 18/10/2026
+ * Author: Shun Suzuki
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: Shun Suzuki (suzuki@hapis.k.u-tokyo.ac.jp)
+ * -----
+ * Copyright (c) 2026 Shun Suzuki. All rights reserved.
+ *
+ */
+
+use ps4000lib::*;
+
+const SAMPLE_RATE: u32 = 1_000_000;
+
+fn open(sim: SimulatedPs4262) -> PS4262<SimulatedPs4262> {
+    let mut ps = PS4262::with_driver(sim).unwrap();
+    ps[Channel::A].range = Range::Range2v;
+    ps
+}
+
+#[test]
+fn envelope_bounds_each_aggregated_group() {
+    let mut ps =
+        open(SimulatedPs4262::new().with_signal(Channel::A, Signal::sine(1_000.0, 1_000.0)));
+    ps.set_downsampling(Downsampling::Aggregate(1000)).unwrap();
+
+    let data = ps.collect_block_immediate(4000, SAMPLE_RATE).unwrap();
+
+    let (min, max) = data.envelope(Channel::A);
+    let (min, max): (Vec<_>, Vec<_>) = (min.collect(), max.collect());
+    assert_eq!(min.len(), 4);
+    assert_eq!(max.len(), 4);
+    assert!(min.iter().all(|mv| (mv + 1_000.0).abs() < 5.0));
+    assert!(max.iter().all(|mv| (mv - 1_000.0).abs() < 5.0));
+    assert!(data.get_min_mv(Channel::A).eq(min.iter().copied()));
+    assert!(data.get_max_mv(Channel::A).eq(max.iter().copied()));
+}
+
+#[test]
+fn without_aggregation_minimum_and_maximum_are_the_samples() {
+    let mut ps =
+        open(SimulatedPs4262::new().with_signal(Channel::A, Signal::sine(1_000.0, 1_000.0)));
+
+    for downsampling in [Downsampling::None, Downsampling::Average(10)] {
+        ps.set_downsampling(downsampling).unwrap();
+
+        let data = ps.collect_block_immediate(1000, SAMPLE_RATE).unwrap();
+
+        assert_eq!(data.get_min_adc(Channel::A), data.get_adc(Channel::A));
+        assert_eq!(data.get_max_adc(Channel::A), data.get_adc(Channel::A));
+        assert!(data.get_min_mv(Channel::A).eq(data.get_mv(Channel::A)));
+        let (min, max) = data.envelope(Channel::A);
+        assert!(min.eq(max));
+    }
+}
+
+#[test]
+fn disabled_channels_return_empty_slices() {
+    let mut ps = open(SimulatedPs4262::new());
+    ps[Channel::B].enable = false;
+
+    let data = ps.collect_block_immediate(1000, SAMPLE_RATE).unwrap();
+
+    assert_eq!(data.get_adc(Channel::A).len(), 1000);
+    assert!(data.get_adc(Channel::B).is_empty());
+    assert!(data.get_min_adc(Channel::B).is_empty());
+    assert_eq!(data.get_mv(Channel::B).count(), 0);
+    let (min, max) = data.envelope(Channel::B);
+    assert_eq!((min.count(), max.count()), (0, 0));
+}
+
+#[test]
+fn channels_share_one_contiguous_buffer() {
+    let ps = open(SimulatedPs4262::new());
+
+    let data = ps.collect_block_immediate(1000, SAMPLE_RATE).unwrap();
+
+    let (a, b) = (data.get_adc(Channel::A), data.get_adc(Channel::B));
+    assert_eq!(a.as_ptr_range().end, b.as_ptr());
+}
+
+#[test]
+fn dropped_data_returns_its_buffers_to_the_pool() {
+    let mut ps = open(SimulatedPs4262::new());
+    let pool = BufferPool::new();
+    ps.set_buffer_pool(pool.clone());
+
+    let data = ps.collect_block_immediate(1000, SAMPLE_RATE).unwrap();
+    assert_eq!(pool.available(), 0);
+    let buffer = data.get_adc(Channel::A).as_ptr();
+    drop(data);
+    assert_eq!(pool.available(), 1);
+
+    let data = ps.collect_block_immediate(1000, SAMPLE_RATE).unwrap();
+    assert_eq!(pool.available(), 0);
+    assert_eq!(data.get_adc(Channel::A).as_ptr(), buffer);
+    drop(data);
+
+    ps.set_downsampling(Downsampling::Aggregate(10)).unwrap();
+    let data = ps.collect_block_immediate(1000, SAMPLE_RATE).unwrap();
+    drop(data);
+    assert_eq!(pool.available(), 2);
+}
@@ -0,0 +1,89 @@
+/*
+ * File: captured_block.rs
+ * Project: tests
+ * Created Date: 18/10/2026
+ * Author: Shun Suzuki
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: Shun Suzuki (suzuki@hapis.k.u-tokyo.ac.jp)
+ * -----
+ * Copyright (c) 2026 Shun Suzuki. All rights reserved.
+ *
+ */
+
+use std::time::Duration;
+
+use ps4000lib::*;
+
+const SAMPLE_RATE: u32 = 1_000_000;
+
+fn open() -> PS4262<SimulatedPs4262> {
+    let mut ps = PS4262::with_driver(
+        SimulatedPs4262::new().with_signal(Channel::A, Signal::sine(1_000.0, 1_000.0)),
+    )
+    .unwrap();
+    ps[Channel::A].range = Range::Range2v;
+    ps
+}
+
+#[test]
+fn chunks_read_the_same_samples_as_the_whole_block() {
+    let mut ps = open();
+    let block = ps.capture_block(1000, SAMPLE_RATE).unwrap();
+
+    assert_eq!(block.sample_count(), 1000);
+    assert_eq!(block.time_interval(), Duration::from_micros(1));
+    let whole = block.read(0..1000, Downsampling::None).unwrap();
+    let chunks = [0..300, 300..700, 700..1000].map(|r| block.read(r, Downsampling::None).unwrap());
+
+    assert_eq!(chunks.each_ref().map(|c| c.len()), [300, 400, 300]);
+    let joined: Vec<i16> = chunks
+        .iter()
+        .flat_map(|c| c.get_adc(Channel::A).iter().copied())
+        .collect();
+    assert_eq!(joined, whole.get_adc(Channel::A));
+}
+
+#[test]
+fn the_block_can_be_read_again_with_other_downsampling() {
+    let mut ps = open();
+    let block = ps.capture_block(1000, SAMPLE_RATE).unwrap();
+
+    let raw = block.read(0..1000, Downsampling::None).unwrap();
+    let aggregated = block.read(0..1000, Downsampling::Aggregate(100)).unwrap();
+    let averaged = block.read(200..400, Downsampling::Average(100)).unwrap();
+
+    assert_eq!(aggregated.len(), 10);
+    for (i, group) in raw.get_adc(Channel::A).chunks(100).enumerate() {
+        assert_eq!(
+            aggregated.get_max_adc(Channel::A)[i],
+            *group.iter().max().unwrap()
+        );
+        assert_eq!(
+            aggregated.get_min_adc(Channel::A)[i],
+            *group.iter().min().unwrap()
+        );
+    }
+    assert_eq!(averaged.len(), 2);
+    for (i, group) in raw.get_adc(Channel::A)[200..400].chunks(100).enumerate() {
+        let mean = group.iter().map(|&x| x as i64).sum::<i64>() / 100;
+        assert_eq!(averaged.get_adc(Channel::A)[i] as i64, mean);
+    }
+}
+
+#[test]
+fn reads_outside_the_capture_are_rejected() {
+    let mut ps = open();
+    let block = ps.capture_block(1000, SAMPLE_RATE).unwrap();
+
+    for samples in [0..1001, 500..500, 1000..1001] {
+        assert!(matches!(
+            block.read(samples, Downsampling::None),
+            Err(Error::InvalidConfig(_))
+        ));
+    }
+    assert!(matches!(
+        block.read(0..1000, Downsampling::Aggregate(0)),
+        Err(Error::InvalidConfig(_))
+    ));
+}
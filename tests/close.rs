@@ -0,0 +1,65 @@
+/*
+ * File: close.rs
+ * Project: tests
+ * Created Date: 18/10/2026
+ * Author: Shun Suzuki
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: Shun Suzuki (suzuki@hapis.k.u-tokyo.ac.jp)
+ * -----
+ * Copyright (c) 2026 Shun Suzuki. All rights reserved.
+ *
+ */
+
+use std::time::Duration;
+
+use ps4000lib::*;
+
+const SAMPLE_RATE: u32 = 1_000_000;
+
+fn open(sim: SimulatedPs4262) -> PS4262<SimulatedPs4262> {
+    PS4262::with_driver(sim).unwrap()
+}
+
+fn never_firing() -> Trigger {
+    Trigger::new(Channel::A, ThresholdDirection::Rising, 1_000.0)
+}
+
+#[test]
+fn dropping_the_device_completes_a_pending_capture() {
+    let ps = open(SimulatedPs4262::new().with_signal(Channel::A, Signal::dc(0.0)));
+    let mut pending = ps
+        .collect_block_triggered(1000, SAMPLE_RATE, never_firing())
+        .unwrap();
+
+    drop(ps);
+
+    assert!(matches!(
+        pending.wait_timeout(Duration::from_secs(5)),
+        Err(Error::DeviceClosed)
+    ));
+}
+
+#[test]
+fn dropping_the_device_ends_the_stream() {
+    let ps = open(SimulatedPs4262::new());
+    let mut stream = ps.start_streaming(StreamConfig::new(SAMPLE_RATE)).unwrap();
+    assert!(stream.next().unwrap().is_ok());
+
+    drop(ps);
+
+    // the worker exits, so that the iteration ends after reporting the closed device
+    let last = stream.by_ref().find(|chunk| chunk.is_err());
+    assert!(matches!(last, Some(Err(Error::DeviceClosed))));
+    assert!(stream.next().is_none());
+}
+
+#[test]
+fn closing_twice_is_harmless_and_later_captures_fail() {
+    let mut ps = open(SimulatedPs4262::new());
+
+    ps.close().unwrap();
+    ps.close().unwrap();
+
+    assert!(ps.collect_block_immediate(1000, SAMPLE_RATE).is_err());
+}
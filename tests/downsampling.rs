@@ -0,0 +1,92 @@
+/*
+ * File: downsampling.rs
+ * Project: tests
+ * Created Date: 18/10/2026
+ * Author: Shun Suzuki
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: Shun Suzuki (suzuki@hapis.k.u-tokyo.ac.jp)
+ * -----
+ * Copyright (c) 2026 Shun Suzuki. All rights reserved.
+ *
+ */
+
+use ps4000lib::*;
+
+const SAMPLE_RATE: u32 = 1_000_000;
+
+// one period of the square wave per 1000 samples
+fn open() -> PS4262<SimulatedPs4262> {
+    let mut ps = PS4262::with_driver(
+        SimulatedPs4262::new().with_signal(Channel::A, Signal::square(1_000.0, 1_000.0)),
+    )
+    .unwrap();
+    ps[Channel::A].range = Range::Range2v;
+    ps
+}
+
+#[test]
+fn aggregate_returns_the_minimum_and_maximum_of_each_group() {
+    let mut ps = open();
+    ps.set_downsampling(Downsampling::Aggregate(1000)).unwrap();
+
+    let data = ps.collect_block_immediate(3000, SAMPLE_RATE).unwrap();
+
+    assert_eq!(data.len(), 3);
+    assert_eq!(data.downsampling(), Downsampling::Aggregate(1000));
+    assert_eq!(data.get_max_adc(Channel::A).len(), 3);
+    assert_eq!(data.get_min_adc(Channel::A).len(), 3);
+    assert!(data
+        .get_max_mv(Channel::A)
+        .all(|mv| (mv - 1_000.0).abs() < 1.0));
+    assert!(data
+        .get_min_mv(Channel::A)
+        .all(|mv| (mv + 1_000.0).abs() < 1.0));
+}
+
+#[test]
+fn average_returns_the_mean_of_each_group() {
+    let mut ps = open();
+    ps.set_downsampling(Downsampling::Average(1000)).unwrap();
+
+    let data = ps.collect_block_immediate(3000, SAMPLE_RATE).unwrap();
+
+    assert_eq!(data.len(), 3);
+    assert!(data.get_mv(Channel::A).all(|mv| mv.abs() < 1.0));
+}
+
+#[test]
+fn a_partial_last_group_yields_one_more_value() {
+    let mut ps = open();
+
+    for downsampling in [Downsampling::Aggregate(300), Downsampling::Average(300)] {
+        ps.set_downsampling(downsampling).unwrap();
+
+        let data = ps.collect_block_immediate(1000, SAMPLE_RATE).unwrap();
+
+        assert_eq!(data.len(), 4);
+        assert_eq!(data.get_adc(Channel::A).len(), 4);
+        assert_eq!(data.get_adc(Channel::B).len(), 4);
+    }
+}
+
+#[test]
+fn a_ratio_above_the_driver_maximum_is_rejected() {
+    let mut ps = open();
+    ps.set_downsampling(Downsampling::Aggregate(2000)).unwrap();
+
+    assert!(matches!(
+        ps.collect_block_immediate(1000, SAMPLE_RATE),
+        Err(Error::InvalidConfig(_))
+    ));
+}
+
+#[test]
+fn a_zero_ratio_is_rejected() {
+    let mut ps = open();
+
+    assert!(matches!(
+        ps.set_downsampling(Downsampling::Average(0)),
+        Err(Error::InvalidConfig(_))
+    ));
+}
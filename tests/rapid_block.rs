@@ -0,0 +1,84 @@
+/*
+ * File: rapid_block.rs
+ * Project: tests
+ * Created Date: 18/10/2026
+ * Author: Shun Suzuki
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: Shun Suzuki (suzuki@hapis.k.u-tokyo.ac.jp)
+ * -----
+ * Copyright (c) 2026 Shun Suzuki. All rights reserved.
+ *
+ */
+
+use std::time::Duration;
+
+use ps4000lib::*;
+
+const SAMPLE_RATE: u32 = 1_000_000;
+
+fn open(sim: SimulatedPs4262) -> PS4262<SimulatedPs4262> {
+    PS4262::with_driver(sim).unwrap()
+}
+
+fn never_firing() -> Trigger {
+    Trigger::new(Channel::A, ThresholdDirection::Rising, 1_000.0)
+}
+
+#[test]
+fn each_segment_is_triggered() {
+    let mut ps =
+        open(SimulatedPs4262::new().with_signal(Channel::A, Signal::square(1_000.0, 1_000.0)));
+    ps[Channel::A].range = Range::Range2v;
+    let trigger = Trigger::new(Channel::A, ThresholdDirection::Rising, 0.0)
+        .with_no_of_pre_trigger_samples(100);
+
+    let blocks = ps
+        .collect_rapid_block(8, 1000, SAMPLE_RATE, trigger)
+        .unwrap()
+        .wait_timeout(Duration::from_secs(5))
+        .unwrap();
+
+    assert_eq!(blocks.len(), 8);
+    for block in &blocks {
+        assert_eq!(block.len(), 1000);
+        let adc = block.get_adc(Channel::A);
+        assert!(adc[..90].iter().all(|&v| v < 0));
+        assert!(adc[110..200].iter().all(|&v| v > 0));
+    }
+}
+
+#[test]
+fn zero_segments_are_rejected() {
+    let ps = open(SimulatedPs4262::new());
+
+    assert!(matches!(
+        ps.collect_rapid_block(0, 1000, SAMPLE_RATE, never_firing()),
+        Err(Error::InvalidConfig(_))
+    ));
+}
+
+#[test]
+fn block_after_cancelled_rapid_block_uses_the_whole_memory() {
+    let ps = open(SimulatedPs4262::new().with_signal(Channel::A, Signal::dc(0.0)));
+
+    ps.collect_rapid_block(64, 1000, SAMPLE_RATE, never_firing())
+        .unwrap()
+        .cancel()
+        .unwrap();
+
+    let data = ps.collect_block_immediate(100_000, SAMPLE_RATE).unwrap();
+    assert_eq!(data.len(), 100_000);
+}
+
+#[test]
+fn block_after_rejected_rapid_block_uses_the_whole_memory() {
+    let ps = open(SimulatedPs4262::new().with_signal(Channel::A, Signal::dc(0.0)));
+
+    assert!(ps
+        .collect_rapid_block(64, 1_000_000, SAMPLE_RATE, never_firing())
+        .is_err());
+
+    let data = ps.collect_block_immediate(100_000, SAMPLE_RATE).unwrap();
+    assert_eq!(data.len(), 100_000);
+}
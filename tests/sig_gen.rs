@@ -0,0 +1,61 @@
+/*
+ * File: sig_gen.rs
+ * Project: tests
+ * Created Date: 18/10/2026
+ * Author: Shun Suzuki
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: Shun Suzuki (suzuki@hapis.k.u-tokyo.ac.jp)
+ * -----
+ * Copyright (c) 2026 Shun Suzuki. All rights reserved.
+ *
+ */
+
+use std::time::Duration;
+
+use ps4000lib::*;
+
+fn open(variant: Variant) -> Ps4000Device<SimulatedPs4262> {
+    Ps4000Device::with_driver(SimulatedPs4262::new().with_variant(variant)).unwrap()
+}
+
+#[test]
+fn arbitrary_waveform_within_the_limits_is_accepted() {
+    let ps = open(Variant::PS4262);
+    let sig_gen = ps.signal_generator().unwrap();
+
+    let burst = ArbitraryWaveform::tone_burst(Duration::from_millis(1), 1000, 5_000.0, 3);
+    sig_gen.set_arbitrary_waveform(&burst, 2_000_000).unwrap();
+}
+
+#[test]
+fn arbitrary_frequency_beyond_the_variant_limit_is_rejected() {
+    let ps = open(Variant::PS4262);
+    let sig_gen = ps.signal_generator().unwrap();
+    let samples = [0.0, 1.0, 0.0, -1.0].repeat(10);
+
+    assert!(matches!(
+        sig_gen.set_arbitrary(&samples, 1e6, 2_000_000),
+        Err(Error::InvalidConfig(_))
+    ));
+    // allowed on the faster generator of the 4226
+    open(Variant::PS4226)
+        .signal_generator()
+        .unwrap()
+        .set_arbitrary(&samples, 50_000.0, 2_000_000)
+        .unwrap();
+}
+
+#[test]
+fn arbitrary_frequency_must_be_finite_and_positive() {
+    let ps = open(Variant::PS4262);
+    let sig_gen = ps.signal_generator().unwrap();
+    let samples = [0.0, 1.0, 0.0, -1.0].repeat(10);
+
+    for frequency in [0.0, -1_000.0, f64::NAN, f64::INFINITY] {
+        assert!(matches!(
+            sig_gen.set_arbitrary(&samples, frequency, 2_000_000),
+            Err(Error::InvalidConfig(_))
+        ));
+    }
+}
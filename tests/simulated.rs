@@ -0,0 +1,137 @@
+/*
+ * File: simulated.rs
+ * Project: tests
+ * Created Date: 18/10/2026
+ * Author: Shun Suzuki
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: Shun Suzuki (suzuki@hapis.k.u-tokyo.ac.jp)
+ * -----
+ * Copyright (c) 2026 Shun Suzuki. All rights reserved.
+ *
+ */
+
+use std::time::Duration;
+
+use ps4000lib::*;
+
+const SAMPLE_RATE: u32 = 1_000_000;
+
+fn open(sim: SimulatedPs4262) -> PS4262<SimulatedPs4262> {
+    PS4262::with_driver(sim).unwrap()
+}
+
+fn mean(data: &BlockData, channel: Channel, range: std::ops::Range<usize>) -> f64 {
+    let len = range.len() as f64;
+    data.get_mv(channel)
+        .skip(range.start)
+        .take(range.len())
+        .sum::<f64>()
+        / len
+}
+
+#[test]
+fn immediate_block_reads_the_signal() {
+    let mut ps = open(SimulatedPs4262::new().with_signal(Channel::A, Signal::dc(1_000.0)));
+    ps[Channel::A].range = Range::Range2v;
+
+    let data = ps.collect_block_immediate(1000, SAMPLE_RATE).unwrap();
+
+    assert_eq!(data.len(), 1000);
+    assert_eq!(data.time_interval(), Duration::from_micros(1));
+    assert!(!data.overflow());
+    assert!((mean(&data, Channel::A, 0..1000) - 1_000.0).abs() < 1.0);
+}
+
+#[test]
+fn input_beyond_the_range_clips_and_overflows() {
+    let mut ps = open(SimulatedPs4262::new().with_signal(Channel::A, Signal::dc(3_000.0)));
+    ps[Channel::A].range = Range::Range2v;
+    ps[Channel::B].enable = false;
+
+    let data = ps.collect_block_immediate(1000, SAMPLE_RATE).unwrap();
+
+    assert!(data.overflow());
+    let max = ps.variant().max_value() as i16;
+    assert!(data.get_adc(Channel::A).iter().all(|&v| v == max));
+    assert!(data.get_mv(Channel::A).all(|mv| (mv - 2_000.0).abs() < 1.0));
+}
+
+#[test]
+fn negative_overflow_clips_at_the_minimum() {
+    let mut ps = open(SimulatedPs4262::new().with_signal(Channel::A, Signal::dc(-3_000.0)));
+    ps[Channel::A].range = Range::Range2v;
+
+    let data = ps.collect_block_immediate(1000, SAMPLE_RATE).unwrap();
+
+    assert!(data.overflow());
+    let min = -(ps.variant().max_value() as i16);
+    assert!(data.get_adc(Channel::A).iter().all(|&v| v == min));
+}
+
+#[test]
+fn matching_probe_attenuation_recovers_the_voltage() {
+    let mut ps = open(
+        SimulatedPs4262::new()
+            .with_signal(Channel::A, Signal::dc(8_000.0))
+            .with_probe(Channel::A, Attenuation::X10),
+    );
+    ps[Channel::A].range = Range::Range10v;
+    ps[Channel::A].attenuation = Attenuation::X10;
+
+    let data = ps.collect_block_immediate(1000, SAMPLE_RATE).unwrap();
+
+    assert!(!data.overflow());
+    assert!((mean(&data, Channel::A, 0..1000) - 8_000.0).abs() < 10.0);
+}
+
+#[test]
+fn mismatched_probe_attenuation_reads_the_attenuated_voltage() {
+    let mut ps = open(
+        SimulatedPs4262::new()
+            .with_signal(Channel::A, Signal::dc(8_000.0))
+            .with_probe(Channel::A, Attenuation::X10),
+    );
+    ps[Channel::A].range = Range::Range1v;
+
+    let data = ps.collect_block_immediate(1000, SAMPLE_RATE).unwrap();
+
+    assert!(!data.overflow());
+    assert!((mean(&data, Channel::A, 0..1000) - 800.0).abs() < 1.0);
+}
+
+#[test]
+fn rising_trigger_fires_on_the_rising_edge() {
+    let mut ps =
+        open(SimulatedPs4262::new().with_signal(Channel::A, Signal::square(1_000.0, 1_000.0)));
+    ps[Channel::A].range = Range::Range2v;
+    let trigger = Trigger::new(Channel::A, ThresholdDirection::Rising, 0.0)
+        .with_no_of_pre_trigger_samples(100);
+
+    let data = ps
+        .collect_block_triggered(1000, SAMPLE_RATE, trigger)
+        .unwrap()
+        .wait_timeout(Duration::from_secs(5))
+        .unwrap();
+
+    assert!(mean(&data, Channel::A, 0..90) < -900.0);
+    assert!(mean(&data, Channel::A, 110..200) > 900.0);
+}
+
+#[test]
+fn falling_trigger_fires_on_the_falling_edge() {
+    let mut ps =
+        open(SimulatedPs4262::new().with_signal(Channel::A, Signal::square(1_000.0, 1_000.0)));
+    ps[Channel::A].range = Range::Range2v;
+    let trigger = Trigger::new(Channel::A, ThresholdDirection::Falling, 0.0)
+        .with_no_of_pre_trigger_samples(100);
+
+    let data = ps
+        .collect_block_triggered(1000, SAMPLE_RATE, trigger)
+        .unwrap()
+        .wait_timeout(Duration::from_secs(5))
+        .unwrap();
+
+    assert!(mean(&data, Channel::A, 0..90) > 900.0);
+    assert!(mean(&data, Channel::A, 110..200) < -900.0);
+}
@@ -0,0 +1,142 @@
+/*
+ * File: streaming.rs
+ * Project: tests
+ * Created Date: 18/10/2026
+ * Author: Shun Suzuki
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: Shun Suzuki (suzuki@hapis.k.u-tokyo.ac.jp)
+ * -----
+ * Copyright (c) 2026 Shun Suzuki. All rights reserved.
+ *
+ */
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use ps4000lib::*;
+
+const SAMPLE_RATE: u32 = 1_000_000;
+
+fn open(sim: SimulatedPs4262) -> PS4262<SimulatedPs4262> {
+    let mut ps = PS4262::with_driver(sim).unwrap();
+    ps[Channel::A].range = Range::Range2v;
+    ps
+}
+
+fn collect(stream: StreamHandle) -> Vec<StreamChunk> {
+    stream.map(|chunk| chunk.unwrap()).collect()
+}
+
+#[test]
+fn auto_stop_ends_the_stream_after_the_samples() {
+    let ps = open(SimulatedPs4262::new());
+    let stream = ps
+        .start_streaming(StreamConfig::new(SAMPLE_RATE).with_auto_stop(5000))
+        .unwrap();
+    assert_eq!(stream.time_interval(), Duration::from_micros(1));
+
+    let chunks = collect(stream);
+
+    assert_eq!(chunks.iter().map(StreamChunk::len).sum::<usize>(), 5000);
+    let mut start = 0;
+    for chunk in &chunks {
+        assert_eq!(chunk.start(), start);
+        start += chunk.len() as u64;
+    }
+    assert!(chunks.last().unwrap().auto_stop());
+    assert!(chunks[..chunks.len() - 1].iter().all(|c| !c.auto_stop()));
+}
+
+#[test]
+fn trigger_point_is_reported_in_its_chunk() {
+    let ps = open(SimulatedPs4262::new().with_signal(Channel::A, Signal::square(1_000.0, 1_000.0)));
+    let trigger = Trigger::new(Channel::A, ThresholdDirection::Rising, 0.0)
+        .with_no_of_pre_trigger_samples(100);
+    let stream = ps
+        .start_streaming(
+            StreamConfig::new(SAMPLE_RATE)
+                .with_trigger(trigger)
+                .with_auto_stop(2000),
+        )
+        .unwrap();
+
+    let chunks = collect(stream);
+
+    let triggered = chunks
+        .iter()
+        .filter_map(|c| c.trigger_at().map(|at| c.start() as usize + at))
+        .collect::<Vec<_>>();
+    assert_eq!(triggered.len(), 1);
+    let at = triggered[0];
+    let mv = chunks
+        .iter()
+        .flat_map(|c| c.get_mv(Channel::A))
+        .collect::<Vec<_>>();
+    assert_eq!(mv.len(), at + 2000);
+    assert!(mv[at - 1] < 0.0);
+    assert!(mv[at] > 0.0);
+}
+
+#[test]
+fn overflow_is_reported_per_chunk() {
+    let ps = open(SimulatedPs4262::new().with_signal(Channel::A, Signal::dc(3_000.0)));
+    let chunks = collect(
+        ps.start_streaming(StreamConfig::new(SAMPLE_RATE).with_auto_stop(2000))
+            .unwrap(),
+    );
+    assert!(chunks.iter().all(StreamChunk::overflow));
+    assert!(chunks
+        .iter()
+        .flat_map(|c| c.get_mv(Channel::A))
+        .all(|mv| (mv - 2_000.0).abs() < 1.0));
+
+    let ps = open(SimulatedPs4262::new().with_signal(Channel::A, Signal::dc(1_000.0)));
+    let chunks = collect(
+        ps.start_streaming(StreamConfig::new(SAMPLE_RATE).with_auto_stop(2000))
+            .unwrap(),
+    );
+    assert!(chunks.iter().all(|c| !c.overflow()));
+}
+
+#[test]
+fn callback_receives_every_chunk() {
+    let ps = open(SimulatedPs4262::new().with_signal(Channel::A, Signal::dc(1_000.0)));
+    let received = Arc::new(Mutex::new(Vec::new()));
+
+    let stream = {
+        let received = received.clone();
+        ps.start_streaming_with_callback(
+            StreamConfig::new(SAMPLE_RATE).with_auto_stop(5000),
+            move |chunk| received.lock().unwrap().push(chunk),
+        )
+        .unwrap()
+    };
+    stream.wait().unwrap();
+
+    let received = received.lock().unwrap();
+    assert_eq!(received.iter().map(StreamChunk::len).sum::<usize>(), 5000);
+    assert!(received.last().unwrap().auto_stop());
+    assert!(received
+        .iter()
+        .flat_map(|c| c.get_mv(Channel::A))
+        .all(|mv| (mv - 1_000.0).abs() < 1.0));
+}
+
+#[test]
+fn stream_without_auto_stop_runs_until_stopped() {
+    let ps = open(SimulatedPs4262::new());
+    let mut stream = ps.start_streaming(StreamConfig::new(SAMPLE_RATE)).unwrap();
+
+    let first = stream.next().unwrap().unwrap();
+    assert!(!first.is_empty());
+    stream.stop().unwrap();
+
+    // the unit is free for block captures again
+    assert_eq!(
+        ps.collect_block_immediate(1000, SAMPLE_RATE).unwrap().len(),
+        1000
+    );
+}
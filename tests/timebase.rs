@@ -0,0 +1,87 @@
+/*
+ * File: timebase.rs
+ * Project: tests
+ * Created Date: 18/10/2026
+ * Author: Shun Suzuki
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: Shun Suzuki (suzuki@hapis.k.u-tokyo.ac.jp)
+ * -----
+ * Copyright (c) 2026 Shun Suzuki. All rights reserved.
+ *
+ */
+
+use std::time::Duration;
+
+use ps4000lib::*;
+
+fn open(variant: Variant) -> Ps4000Device<SimulatedPs4262> {
+    Ps4000Device::with_driver(SimulatedPs4262::new().with_variant(variant)).unwrap()
+}
+
+#[test]
+fn rate_resolves_to_the_nearest_interval() {
+    let ps = open(Variant::PS4262);
+
+    let timebase = ps.timebase_for_rate(3_000_000.0).unwrap();
+
+    assert_eq!(timebase.interval(), Duration::from_nanos(300));
+    assert!((timebase.sample_rate() - 1e9 / 300.).abs() < 1e-3);
+}
+
+#[test]
+fn interval_resolves_across_the_clock_regions() {
+    let ps = open(Variant::PS4226);
+    assert_eq!(
+        ps.timebase_for_interval(Duration::from_nanos(16))
+            .unwrap()
+            .interval(),
+        Duration::from_nanos(16)
+    );
+    assert_eq!(
+        ps.timebase_for_interval(Duration::from_nanos(30))
+            .unwrap()
+            .interval(),
+        Duration::from_nanos(32)
+    );
+}
+
+#[test]
+fn rates_beyond_the_limits_resolve_to_the_fastest_or_slowest_timebase() {
+    let ps = open(Variant::PS4262);
+
+    assert_eq!(ps.timebase_for_rate(1e12).unwrap().timebase(), 0);
+    let slowest = ps.timebase_for_rate(1e-3).unwrap();
+    assert_eq!(
+        slowest.timebase(),
+        ps.timebase_for_rate(1e-9).unwrap().timebase()
+    );
+    assert!(slowest.timebase() > ps.timebase_for_rate(1.0).unwrap().timebase());
+}
+
+#[test]
+fn non_positive_rates_are_rejected() {
+    let ps = open(Variant::PS4262);
+
+    for rate in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+        assert!(matches!(
+            ps.timebase_for_rate(rate),
+            Err(Error::InvalidConfig(_))
+        ));
+    }
+    assert!(matches!(
+        ps.timebase_for_interval(Duration::ZERO),
+        Err(Error::InvalidConfig(_))
+    ));
+}
+
+#[test]
+fn max_samples_are_shared_by_the_enabled_channels() {
+    let mut ps = open(Variant::PS4262);
+    let both = ps.timebase_for_rate(1e6).unwrap().max_samples();
+
+    ps[Channel::B].enable = false;
+    let one = ps.timebase_for_rate(1e6).unwrap().max_samples();
+
+    assert_eq!(one, both * 2);
+}
@@ -0,0 +1,253 @@
+/*
+ * File: trigger.rs
+ * Project: tests
+ * Created Date: 18/10/2026
+ * Author: Shun Suzuki
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: Shun Suzuki (suzuki@hapis.k.u-tokyo.ac.jp)
+ * -----
+ * Copyright (c) 2026 Shun Suzuki. All rights reserved.
+ *
+ */
+
+use std::time::Duration;
+
+use ps4000lib::*;
+
+const SAMPLE_RATE: u32 = 1_000_000;
+
+fn open(sim: SimulatedPs4262) -> PS4262<SimulatedPs4262> {
+    PS4262::with_driver(sim).unwrap()
+}
+
+fn mean(data: &BlockData, channel: Channel, range: std::ops::Range<usize>) -> f64 {
+    let len = range.len() as f64;
+    data.get_mv(channel)
+        .skip(range.start)
+        .take(range.len())
+        .sum::<f64>()
+        / len
+}
+
+fn pulse_trigger(qualifier: PulseWidthQualifier) -> AdvancedTrigger {
+    AdvancedTrigger::new()
+        .with_level(Channel::A, ThresholdDirection::Rising, 500.0)
+        .with_condition(
+            TriggerCondition::new()
+                .with(Channel::A, TriggerState::True)
+                .with_pulse_width_qualifier(TriggerState::True),
+        )
+        .with_pulse_width_qualifier(
+            qualifier.with_condition(TriggerCondition::new().with(Channel::A, TriggerState::True)),
+        )
+        .with_no_of_pre_trigger_samples(100)
+}
+
+fn pulse_device() -> PS4262<SimulatedPs4262> {
+    let mut ps = open(SimulatedPs4262::new().with_signal(
+        Channel::A,
+        Signal::pulse(1_000.0, Duration::from_micros(50), 1_000.0),
+    ));
+    ps[Channel::A].range = Range::Range2v;
+    ps
+}
+
+#[test]
+fn pulse_width_qualifier_fires_at_the_end_of_a_short_pulse() {
+    let ps = pulse_device();
+    let trigger = pulse_trigger(PulseWidthQualifier::less_than(Duration::from_micros(100)));
+
+    let data = ps
+        .collect_block_triggered(1000, SAMPLE_RATE, trigger)
+        .unwrap()
+        .wait_timeout(Duration::from_secs(5))
+        .unwrap();
+
+    // the 50 us pulse ends at the trigger point
+    assert!(mean(&data, Channel::A, 60..90) > 900.0);
+    assert!(mean(&data, Channel::A, 110..200) < 100.0);
+}
+
+#[test]
+fn pulse_width_qualifier_rejects_a_short_pulse() {
+    let ps = pulse_device();
+    let trigger = pulse_trigger(PulseWidthQualifier::greater_than(Duration::from_micros(
+        100,
+    )));
+
+    let mut handle = ps
+        .collect_block_triggered(1000, SAMPLE_RATE, trigger)
+        .unwrap();
+
+    assert!(matches!(
+        handle.wait_timeout(Duration::from_millis(500)),
+        Err(Error::Timeout)
+    ));
+}
+
+#[test]
+fn pulse_width_qualifier_in_range_fires_on_a_pulse_inside_it() {
+    let ps = pulse_device();
+    let trigger = pulse_trigger(PulseWidthQualifier::in_range(
+        Duration::from_micros(30),
+        Duration::from_micros(70),
+    ));
+
+    ps.collect_block_triggered(1000, SAMPLE_RATE, trigger)
+        .unwrap()
+        .wait_timeout(Duration::from_secs(5))
+        .unwrap();
+}
+
+#[test]
+fn pulse_width_qualifier_out_of_range_rejects_a_pulse_inside_it() {
+    let ps = pulse_device();
+    let trigger = pulse_trigger(PulseWidthQualifier::out_of_range(
+        Duration::from_micros(30),
+        Duration::from_micros(70),
+    ));
+
+    let mut handle = ps
+        .collect_block_triggered(1000, SAMPLE_RATE, trigger)
+        .unwrap();
+
+    assert!(matches!(
+        handle.wait_timeout(Duration::from_millis(500)),
+        Err(Error::Timeout)
+    ));
+}
+
+fn two_channels(a: Signal, b: Signal) -> PS4262<SimulatedPs4262> {
+    let mut ps = open(
+        SimulatedPs4262::new()
+            .with_signal(Channel::A, a)
+            .with_signal(Channel::B, b),
+    );
+    ps[Channel::A].range = Range::Range2v;
+    ps[Channel::B].range = Range::Range2v;
+    ps
+}
+
+fn fires(ps: &PS4262<SimulatedPs4262>, trigger: impl Into<TriggerConfig>) -> Option<BlockData> {
+    let mut handle = ps
+        .collect_block_triggered(1000, SAMPLE_RATE, trigger)
+        .unwrap();
+    match handle.wait_timeout(Duration::from_millis(500)) {
+        Ok(data) => Some(data),
+        Err(Error::Timeout) => None,
+        Err(e) => panic!("{}", e),
+    }
+}
+
+#[test]
+fn any_condition_fires_the_trigger() {
+    let ps = two_channels(Signal::dc(0.0), Signal::square(1_000.0, 1_000.0));
+    let trigger = AdvancedTrigger::new()
+        .with_level(Channel::A, ThresholdDirection::Rising, 500.0)
+        .with_level(Channel::B, ThresholdDirection::Rising, 0.0)
+        .with_condition(TriggerCondition::new().with(Channel::A, TriggerState::True))
+        .with_condition(TriggerCondition::new().with(Channel::B, TriggerState::True));
+
+    assert!(fires(&ps, trigger).is_some());
+}
+
+#[test]
+fn without_conditions_all_thresholds_must_fire_together() {
+    let trigger = AdvancedTrigger::new()
+        .with_level(Channel::A, ThresholdDirection::Rising, 500.0)
+        .with_level(Channel::B, ThresholdDirection::Rising, 0.0);
+
+    let ps = two_channels(Signal::dc(0.0), Signal::square(1_000.0, 1_000.0));
+    assert!(fires(&ps, trigger.clone()).is_none());
+
+    let ps = two_channels(
+        Signal::square(1_000.0, 1_000.0),
+        Signal::square(1_000.0, 1_000.0),
+    );
+    assert!(fires(&ps, trigger).is_some());
+}
+
+#[test]
+fn window_enter_fires_when_the_signal_enters_the_window() {
+    let ps = two_channels(Signal::sine(1_000.0, 1_000.0), Signal::dc(0.0));
+    let trigger = AdvancedTrigger::new()
+        .with_window(Channel::A, ThresholdDirection::Enter, -200.0, 200.0)
+        .with_no_of_pre_trigger_samples(100);
+
+    let data = fires(&ps, trigger).unwrap();
+    let mv = data.get_mv(Channel::A).collect::<Vec<_>>();
+
+    assert!(mv[50].abs() > 200.0);
+    assert!(mv[100].abs() < 200.0);
+    assert!(mv[110].abs() < 200.0);
+}
+
+#[test]
+fn window_exit_fires_when_the_signal_leaves_the_window() {
+    let ps = two_channels(Signal::sine(1_000.0, 1_000.0), Signal::dc(0.0));
+    let trigger = AdvancedTrigger::new()
+        .with_window(Channel::A, ThresholdDirection::Exit, -200.0, 200.0)
+        .with_no_of_pre_trigger_samples(100);
+
+    let data = fires(&ps, trigger).unwrap();
+    let mv = data.get_mv(Channel::A).collect::<Vec<_>>();
+
+    assert!(mv[90].abs() < 200.0);
+    assert!(mv[101].abs() > 200.0);
+    assert!(mv[150].abs() > 200.0);
+}
+
+#[test]
+fn window_inside_fires_only_inside_the_window() {
+    let trigger =
+        AdvancedTrigger::new().with_window(Channel::A, ThresholdDirection::Inside, -200.0, 200.0);
+
+    let ps = two_channels(Signal::dc(0.0), Signal::dc(0.0));
+    assert!(fires(&ps, trigger.clone()).is_some());
+
+    let ps = two_channels(Signal::dc(1_000.0), Signal::dc(0.0));
+    assert!(fires(&ps, trigger).is_none());
+}
+
+#[test]
+fn hysteresis_keeps_a_shallow_dip_from_rearming_the_trigger() {
+    // starts high at 1000 mV and dips to -50 mV
+    let signal = Signal::square(1_000.0, 525.0).with_offset_mv(475.0);
+    let trigger = AdvancedTrigger::new().with_level(Channel::A, ThresholdDirection::Rising, 0.0);
+
+    let ps = two_channels(signal, Signal::dc(0.0));
+    assert!(fires(&ps, trigger.clone()).is_some());
+
+    let ps = two_channels(signal, Signal::dc(0.0));
+    assert!(fires(&ps, trigger.with_hysteresis_mv(Channel::A, 100.0)).is_none());
+}
+
+#[test]
+fn condition_on_an_input_without_threshold_is_rejected() {
+    let ps = two_channels(Signal::dc(0.0), Signal::dc(0.0));
+    let trigger = AdvancedTrigger::new()
+        .with_level(Channel::A, ThresholdDirection::Rising, 0.0)
+        .with_condition(TriggerCondition::new().with(Channel::B, TriggerState::True));
+
+    assert!(matches!(
+        ps.collect_block_triggered(1000, SAMPLE_RATE, trigger),
+        Err(Error::InvalidConfig(_))
+    ));
+}
+
+#[test]
+fn direction_must_match_the_threshold_mode() {
+    let ps = two_channels(Signal::dc(0.0), Signal::dc(0.0));
+
+    for trigger in [
+        AdvancedTrigger::new().with_level(Channel::A, ThresholdDirection::Enter, 0.0),
+        AdvancedTrigger::new().with_window(Channel::A, ThresholdDirection::Rising, -200.0, 200.0),
+        AdvancedTrigger::new().with_window(Channel::A, ThresholdDirection::Inside, 200.0, -200.0),
+    ] {
+        assert!(matches!(
+            ps.collect_block_triggered(1000, SAMPLE_RATE, trigger),
+            Err(Error::InvalidConfig(_))
+        ));
+    }
+}
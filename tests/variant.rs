@@ -0,0 +1,169 @@
+/*
+ * File: variant.rs
+ * Project: tests
+ * Created Date: 18/10/2026
+ * Author: Shun Suzuki
+ * -----
+ * Last Modified: 18/10/2026
+ * Modified By: Shun Suzuki (suzuki@hapis.k.u-tokyo.ac.jp)
+ * -----
+ * Copyright (c) 2026 Shun Suzuki. All rights reserved.
+ *
+ */
+
+use ps4000lib::*;
+
+const SAMPLE_RATE: u32 = 1_000_000;
+
+const VARIANTS: [Variant; 7] = [
+    Variant::PS4223,
+    Variant::PS4224,
+    Variant::PS4226,
+    Variant::PS4227,
+    Variant::PS4262,
+    Variant::PS4423,
+    Variant::PS4424,
+];
+
+fn open(variant: Variant) -> Ps4000Device<SimulatedPs4262> {
+    Ps4000Device::with_driver(SimulatedPs4262::new().with_variant(variant)).unwrap()
+}
+
+fn external_trigger() -> AdvancedTrigger {
+    AdvancedTrigger::new()
+        .with_external(ThresholdDirection::Rising)
+        .with_condition(TriggerCondition::new().with(TriggerSource::External, TriggerState::True))
+}
+
+#[test]
+fn the_variant_is_read_from_the_unit() {
+    for variant in VARIANTS {
+        assert_eq!(open(variant).variant(), variant);
+    }
+}
+
+#[test]
+fn ps4262_rejects_other_variants() {
+    let sim = SimulatedPs4262::new().with_variant(Variant::PS4424);
+
+    assert!(matches!(
+        PS4262::with_driver(sim),
+        Err(Error::UnsupportedVariant(_))
+    ));
+}
+
+#[test]
+fn only_the_ranges_of_the_variant_are_accepted() {
+    for variant in VARIANTS {
+        let mut ps = open(variant);
+        for range in [
+            Range::Range10mv,
+            Range::Range50mv,
+            Range::Range20v,
+            Range::Range100v,
+        ] {
+            ps.channel_mut(Channel::A).unwrap().range = range;
+            let result = ps.collect_block_immediate(100, SAMPLE_RATE);
+            if variant.ranges().contains(&range) {
+                assert!(result.is_ok(), "{:?} on {}", range, variant);
+            } else {
+                assert!(
+                    matches!(result, Err(Error::InvalidConfig(_))),
+                    "{:?} on {}",
+                    range,
+                    variant
+                );
+            }
+        }
+    }
+    assert!(Variant::PS4262.ranges().contains(&Range::Range10mv));
+    assert!(!Variant::PS4224.ranges().contains(&Range::Range10mv));
+    assert!(Variant::PS4223.ranges().contains(&Range::Range100v));
+    assert!(!Variant::PS4224.ranges().contains(&Range::Range100v));
+}
+
+#[test]
+fn external_trigger_is_rejected_without_an_ext_input() {
+    for variant in [
+        Variant::PS4223,
+        Variant::PS4224,
+        Variant::PS4423,
+        Variant::PS4424,
+    ] {
+        let ps = open(variant);
+
+        assert!(matches!(
+            ps.collect_block_triggered(100, SAMPLE_RATE, external_trigger()),
+            Err(Error::InvalidConfig(_))
+        ));
+    }
+    let ps = open(Variant::PS4262);
+    assert!(ps
+        .collect_block_triggered(100, SAMPLE_RATE, external_trigger())
+        .is_ok());
+}
+
+#[test]
+fn aux_trigger_is_rejected_on_every_variant() {
+    let trigger = AdvancedTrigger::new()
+        .with_aux(ThresholdDirection::Rising)
+        .with_condition(TriggerCondition::new().with(TriggerSource::Aux, TriggerState::True));
+
+    for variant in VARIANTS {
+        assert!(matches!(
+            open(variant).collect_block_triggered(100, SAMPLE_RATE, trigger.clone()),
+            Err(Error::InvalidConfig(_))
+        ));
+    }
+}
+
+#[test]
+fn channels_c_and_d_exist_only_on_four_channel_models() {
+    for variant in VARIANTS {
+        let mut ps = open(variant);
+        let four = variant.channel_count() == 4;
+        for channel in [Channel::C, Channel::D] {
+            assert_eq!(
+                ps.channel(channel).is_ok(),
+                four,
+                "{:?} on {}",
+                channel,
+                variant
+            );
+            assert_eq!(ps.channel_mut(channel).is_ok(), four);
+        }
+        assert!(ps.channel(Channel::A).is_ok() && ps.channel(Channel::B).is_ok());
+    }
+}
+
+#[test]
+fn trigger_on_channel_c_is_rejected_on_two_channel_models() {
+    let trigger = Trigger::new(Channel::C, ThresholdDirection::Rising, 0.0);
+
+    assert!(matches!(
+        open(Variant::PS4224).collect_block_triggered(100, SAMPLE_RATE, trigger),
+        Err(Error::InvalidConfig(_))
+    ));
+    assert!(open(Variant::PS4424)
+        .collect_block_triggered(100, SAMPLE_RATE, trigger)
+        .is_ok());
+}
+
+#[test]
+fn four_channel_models_capture_channels_c_and_d() {
+    let mut ps = Ps4000Device::with_driver(
+        SimulatedPs4262::new()
+            .with_variant(Variant::PS4424)
+            .with_signal(Channel::C, Signal::dc(1_000.0))
+            .with_signal(Channel::D, Signal::dc(-500.0)),
+    )
+    .unwrap();
+    ps.channel_mut(Channel::C).unwrap().range = Range::Range2v;
+    ps.channel_mut(Channel::D).unwrap().range = Range::Range2v;
+
+    let data = ps.collect_block_immediate(1000, SAMPLE_RATE).unwrap();
+
+    assert_eq!(data.get_adc(Channel::C).len(), 1000);
+    assert!(data.get_mv(Channel::C).all(|mv| (mv - 1_000.0).abs() < 1.0));
+    assert!(data.get_mv(Channel::D).all(|mv| (mv + 500.0).abs() < 1.0));
+}